@@ -0,0 +1,206 @@
+use crate::strict;
+use anyhow::bail;
+use lazy_static::lazy_static;
+use memmap::Mmap;
+use std::borrow::Cow;
+use std::collections::{HashSet, VecDeque};
+use std::fs::File;
+use std::io::{Cursor, Read};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use zip::read::ZipFile;
+use zip::ZipArchive;
+
+const DEFAULT_MAX_OPEN_ARCHIVES: usize = 4;
+const MAX_OPEN_ARCHIVES_ENV: &str = "YA_EMSCRIPTEN_MAX_OPEN_ARCHIVES";
+
+lazy_static! {
+    static ref POOL: Mutex<ArchivePool> = Mutex::new(ArchivePool::new(
+        std::env::var(MAX_OPEN_ARCHIVES_ENV)
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(DEFAULT_MAX_OPEN_ARCHIVES)
+    ));
+}
+
+/// Zip archive read from a memory map of the whole file, so entries are
+/// read lazily without repeated file reads.
+pub type Archive = ZipArchive<Cursor<Mmap>>;
+
+/// Open zip archives, most recently used first.
+struct ArchivePool {
+    capacity: usize,
+    archives: VecDeque<(PathBuf, Archive)>,
+}
+
+impl ArchivePool {
+    fn new(capacity: usize) -> Self {
+        ArchivePool {
+            capacity: capacity.max(1),
+            archives: VecDeque::new(),
+        }
+    }
+
+    fn get(&mut self, path: &Path) -> anyhow::Result<&mut Archive> {
+        match self.archives.iter().position(|(p, _)| p == path) {
+            Some(idx) => {
+                let entry = self.archives.remove(idx).unwrap();
+                self.archives.push_front(entry);
+            }
+            None => {
+                // Safety: deployed images are never modified in place, they
+                // are only replaced as whole files.
+                let map = unsafe { Mmap::map(&File::open(path)?)? };
+                let archive = ZipArchive::new(Cursor::new(map))?;
+                self.archives.push_front((path.to_owned(), archive));
+                self.archives.truncate(self.capacity);
+            }
+        }
+        Ok(&mut self.archives[0].1)
+    }
+}
+
+/// Runs `f` on a pooled reader of the archive at `path`, opening it if needed.
+///
+/// Pool size is bounded by `YA_EMSCRIPTEN_MAX_OPEN_ARCHIVES` (default 4),
+/// least recently used archives are closed first.
+pub fn with_archive<T>(
+    path: &Path,
+    f: impl FnOnce(&mut Archive) -> anyhow::Result<T>,
+) -> anyhow::Result<T> {
+    let mut pool = POOL.lock().unwrap();
+    f(pool.get(path)?)
+}
+
+/// Looks up an entry by its decoded name, then by the raw stored bytes.
+/// The latter finds names that aren't valid UTF-8 (or were stored as cp437).
+fn find<'a>(a: &'a mut Archive, name: &Path) -> anyhow::Result<Option<ZipFile<'a>>> {
+    if let Some(name) = name.to_str() {
+        if a.by_name(name).is_ok() {
+            return Ok(Some(a.by_name(name)?));
+        }
+    }
+    let raw = path_bytes(name);
+    for i in 0..a.len() {
+        if a.by_index(i)?.name_raw() == raw.as_ref() {
+            strict::fallback(
+                "raw-entry-name",
+                format!("{} matched by its raw stored name", name.display()),
+            )?;
+            return Ok(Some(a.by_index(i)?));
+        }
+    }
+    Ok(None)
+}
+
+pub fn has_entry(a: &mut Archive, name: &Path) -> anyhow::Result<bool> {
+    Ok(find(a, name)?.is_some())
+}
+
+pub fn read_entry(a: &mut Archive, name: &Path) -> anyhow::Result<Vec<u8>> {
+    if let Some(mut entry) = find(a, name)? {
+        let mut bytes = Vec::with_capacity(entry.size() as usize);
+        entry.read_to_end(&mut bytes)?;
+        return Ok(bytes);
+    }
+
+    let mut near_misses = Vec::new();
+    for i in 0..a.len() {
+        let entry = a.by_index(i)?;
+        if is_near_miss(entry.name(), &name.to_string_lossy()) {
+            near_misses.push(entry.name().to_string());
+        }
+    }
+    if near_misses.is_empty() {
+        bail!("{} not found in archive", name.display())
+    } else {
+        bail!(
+            "{} not found in archive, similar entries: {}",
+            name.display(),
+            near_misses.join(", ")
+        )
+    }
+}
+
+/// Rejects archives whose member names could escape the package root when
+/// unpacked, or that store the same path twice.
+pub fn check_entries(a: &mut Archive) -> anyhow::Result<()> {
+    let mut names = Vec::with_capacity(a.len());
+    for i in 0..a.len() {
+        names.push(a.by_index(i)?.name_raw().to_vec());
+    }
+    check_names(names.iter().map(Vec::as_slice))
+}
+
+fn check_names<'a>(names: impl IntoIterator<Item = &'a [u8]>) -> anyhow::Result<()> {
+    let mut seen = HashSet::new();
+    let mut files = HashSet::new();
+    let mut dirs = HashSet::new();
+    for raw in names {
+        let name = String::from_utf8_lossy(raw).replace('\\', "/");
+        let is_dir = name.ends_with('/');
+        let parts: Vec<_> = name.split('/').filter(|part| !part.is_empty()).collect();
+        if name.starts_with('/') || parts.first().is_some_and(|part| part.ends_with(':')) {
+            bail!("archive entry {:?} is an absolute path", name);
+        }
+        if parts.iter().any(|part| *part == "..") {
+            bail!("archive entry {:?} points outside of the package", name);
+        }
+        let path = parts.join("/");
+        if !seen.insert(path.clone()) {
+            bail!("archive entry {:?} is stored more than once", name);
+        }
+        for i in 1..parts.len() {
+            dirs.insert(parts[..i].join("/"));
+        }
+        if is_dir {
+            dirs.insert(path);
+        } else {
+            files.insert(path);
+        }
+    }
+    if let Some(path) = files.intersection(&dirs).next() {
+        bail!("archive entry {:?} is both a file and a directory", path);
+    }
+    Ok(())
+}
+
+/// Entry names differing only in case, separators or directory.
+fn is_near_miss(candidate: &str, wanted: &str) -> bool {
+    let file_name = |name: &str| name.rsplit(&['/', '\\'][..]).next().map(str::to_lowercase);
+    candidate.eq_ignore_ascii_case(wanted)
+        || candidate.replace('\\', "/") == wanted
+        || (!candidate.ends_with('/') && file_name(candidate) == file_name(wanted))
+}
+
+#[cfg(unix)]
+fn path_bytes(path: &Path) -> Cow<'_, [u8]> {
+    use std::os::unix::ffi::OsStrExt;
+    Cow::Borrowed(path.as_os_str().as_bytes())
+}
+
+#[cfg(not(unix))]
+fn path_bytes(path: &Path) -> Cow<'_, [u8]> {
+    Cow::Owned(path.to_string_lossy().replace('\\', "/").into_bytes())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn check(names: &[&str]) -> anyhow::Result<()> {
+        check_names(names.iter().map(|name| name.as_bytes()))
+    }
+
+    #[test]
+    fn test_check_names() {
+        assert!(check(&["gu-package.json", "bin/", "bin/ls.wasm", "bin/ls.js"]).is_ok());
+
+        assert!(check(&["../../.bashrc"]).is_err());
+        assert!(check(&["bin/..\\..\\evil.js"]).is_err());
+        assert!(check(&["/etc/passwd"]).is_err());
+        assert!(check(&["C:/Windows/evil.dll"]).is_err());
+        assert!(check(&["bin/ls.wasm", "bin//ls.wasm"]).is_err());
+        assert!(check(&["bin", "bin/ls.wasm"]).is_err());
+    }
+}
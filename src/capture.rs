@@ -0,0 +1,279 @@
+//! Capture of the program's stdout and stderr.
+//!
+//! While a capture is active the runtime's fds 1 and 2 are pipes; whatever
+//! is written to them is copied to `<dir>/stdout` and `<dir>/stderr`. When
+//! streaming, each chunk is also forwarded to the original stdout as a
+//! frame: one byte stream id (1 stdout, 2 stderr), the payload length as a
+//! big-endian u32, and the payload. A failed command ends the stream with
+//! an error frame (id 3) holding the JSON error response.
+//!
+//! The redirect covers the whole process, so the runtime's own diagnostics
+//! go through `RuntimeStderr`, which keeps writing to the original stderr.
+//! A capture abandoned while the engine still runs leaves fds 1 and 2 on
+//! `/dev/null`; the response then goes through `RuntimeStdout`.
+
+use lazy_static::lazy_static;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::Path;
+use std::sync::Mutex;
+
+lazy_static! {
+    /// Duplicate of stdout from before the active capture started.
+    static ref RUNTIME_STDOUT: Mutex<Option<File>> = Mutex::new(None);
+    /// Duplicate of stderr from before the active capture started.
+    static ref RUNTIME_STDERR: Mutex<Option<File>> = Mutex::new(None);
+}
+
+/// Writer for responses: the supervisor's stdout, also after a capture was
+/// abandoned.
+pub struct RuntimeStdout;
+
+/// Writer for events and logs: the supervisor's stderr, also while the
+/// program's output is being captured.
+pub struct RuntimeStderr;
+
+/// Runs `f` on the saved descriptor, or on `fallback` outside of captures.
+/// Under one lock, so lines from different threads don't interleave.
+fn with_saved<R>(
+    saved: &Mutex<Option<File>>,
+    fallback: &mut dyn Write,
+    f: impl FnOnce(&mut dyn Write) -> io::Result<R>,
+) -> io::Result<R> {
+    match saved.lock().unwrap().as_mut() {
+        Some(file) => f(file),
+        None => f(fallback),
+    }
+}
+
+impl Write for RuntimeStdout {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        with_saved(&RUNTIME_STDOUT, &mut io::stdout(), |out| out.write(buf))
+    }
+
+    fn write_all(&mut self, buf: &[u8]) -> io::Result<()> {
+        with_saved(&RUNTIME_STDOUT, &mut io::stdout(), |out| out.write_all(buf))
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        with_saved(&RUNTIME_STDOUT, &mut io::stdout(), |out| out.flush())
+    }
+}
+
+impl Write for RuntimeStderr {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        with_saved(&RUNTIME_STDERR, &mut io::stderr(), |out| out.write(buf))
+    }
+
+    fn write_all(&mut self, buf: &[u8]) -> io::Result<()> {
+        with_saved(&RUNTIME_STDERR, &mut io::stderr(), |out| out.write_all(buf))
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        with_saved(&RUNTIME_STDERR, &mut io::stderr(), |out| out.flush())
+    }
+}
+
+pub const STDOUT_ID: u8 = 1;
+pub const STDERR_ID: u8 = 2;
+pub const ERROR_ID: u8 = 3;
+
+pub fn write_frame(out: &mut impl Write, stream_id: u8, payload: &[u8]) -> io::Result<()> {
+    out.write_all(&[stream_id])?;
+    out.write_all(&(payload.len() as u32).to_be_bytes())?;
+    out.write_all(payload)?;
+    out.flush()
+}
+
+pub struct OutputCapture {
+    #[cfg(unix)]
+    redirects: Vec<unix::Redirect>,
+}
+
+impl OutputCapture {
+    pub fn start(dir: &Path, stream: bool) -> io::Result<Self> {
+        fs::create_dir_all(dir)?;
+        let stdout = File::create(dir.join("stdout"))?;
+        let stderr = File::create(dir.join("stderr"))?;
+        start(stdout, stderr, stream)
+    }
+
+    /// Restores the original descriptors and waits until all captured output
+    /// is written.
+    pub fn finish(mut self) -> io::Result<()> {
+        finish(&mut self)
+    }
+
+    /// Ends the capture of a program that is still running: fds 1 and 2 are
+    /// pointed at `/dev/null` instead of being restored, so its further
+    /// output can't reach the supervisor's stdout. `RuntimeStdout` and
+    /// `RuntimeStderr` keep writing to the original descriptors.
+    pub fn abandon(mut self) -> io::Result<()> {
+        abandon(&mut self)?;
+        // Nothing is left for `Drop` to restore.
+        std::mem::forget(self);
+        Ok(())
+    }
+}
+
+impl Drop for OutputCapture {
+    /// Restores the descriptors of a capture that wasn't finished, e.g. when
+    /// unwinding from a panic.
+    fn drop(&mut self) {
+        let _ = finish(self);
+    }
+}
+
+#[cfg(unix)]
+fn start(stdout: File, stderr: File, stream: bool) -> io::Result<OutputCapture> {
+    use std::sync::Arc;
+
+    let frames = if stream {
+        Some(Arc::new(Mutex::new(unix::dup_file(libc::STDOUT_FILENO)?)))
+    } else {
+        None
+    };
+    *RUNTIME_STDOUT.lock().unwrap() = Some(unix::dup_file(libc::STDOUT_FILENO)?);
+    *RUNTIME_STDERR.lock().unwrap() = Some(unix::dup_file(libc::STDERR_FILENO)?);
+    let stdout = unix::Redirect::new(libc::STDOUT_FILENO, STDOUT_ID, stdout, frames.clone())?;
+    let stderr = unix::Redirect::new(libc::STDERR_FILENO, STDERR_ID, stderr, frames)?;
+    Ok(OutputCapture {
+        redirects: vec![stdout, stderr],
+    })
+}
+
+#[cfg(unix)]
+fn finish(capture: &mut OutputCapture) -> io::Result<()> {
+    let _ = io::stdout().flush();
+    // Safety: flushing all C stdio streams has no preconditions.
+    unsafe { libc::fflush(std::ptr::null_mut()) };
+    for redirect in capture.redirects.drain(..) {
+        redirect.restore()?;
+    }
+    RUNTIME_STDOUT.lock().unwrap().take();
+    RUNTIME_STDERR.lock().unwrap().take();
+    Ok(())
+}
+
+#[cfg(unix)]
+fn abandon(capture: &mut OutputCapture) -> io::Result<()> {
+    use std::os::unix::io::AsRawFd;
+
+    let null = fs::OpenOptions::new().write(true).open("/dev/null")?;
+    for redirect in capture.redirects.drain(..) {
+        redirect.replace(null.as_raw_fd())?;
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn start(_stdout: File, _stderr: File, _stream: bool) -> io::Result<OutputCapture> {
+    log::warn!("output capture is only supported on unix");
+    Ok(OutputCapture {})
+}
+
+#[cfg(not(unix))]
+fn finish(_capture: &mut OutputCapture) -> io::Result<()> {
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn abandon(_capture: &mut OutputCapture) -> io::Result<()> {
+    Ok(())
+}
+
+#[cfg(unix)]
+mod unix {
+    use std::fs::File;
+    use std::io::{self, Read, Write};
+    use std::os::unix::io::{FromRawFd, RawFd};
+    use std::sync::{Arc, Mutex};
+    use std::thread::{self, JoinHandle};
+
+    fn check(ret: libc::c_int) -> io::Result<libc::c_int> {
+        if ret < 0 {
+            Err(io::Error::last_os_error())
+        } else {
+            Ok(ret)
+        }
+    }
+
+    pub fn dup_file(fd: RawFd) -> io::Result<File> {
+        // Safety: the duplicate is a fresh descriptor owned by the File.
+        unsafe { Ok(File::from_raw_fd(check(libc::dup(fd))?)) }
+    }
+
+    /// Descriptor pointed at a pipe drained by a copying thread.
+    pub struct Redirect {
+        fd: RawFd,
+        saved: RawFd,
+        copier: JoinHandle<io::Result<()>>,
+    }
+
+    impl Redirect {
+        pub fn new(
+            fd: RawFd,
+            stream_id: u8,
+            mut file: File,
+            frames: Option<Arc<Mutex<File>>>,
+        ) -> io::Result<Self> {
+            let mut pipe = [0; 2];
+            // Safety: plain descriptor manipulation, every result is checked.
+            let (mut reader, saved) = unsafe {
+                check(libc::pipe(pipe.as_mut_ptr()))?;
+                let saved = check(libc::dup(fd))?;
+                check(libc::dup2(pipe[1], fd))?;
+                libc::close(pipe[1]);
+                (File::from_raw_fd(pipe[0]), saved)
+            };
+
+            let copier = thread::spawn(move || -> io::Result<()> {
+                let mut buf = [0u8; 8192];
+                loop {
+                    let n = reader.read(&mut buf)?;
+                    if n == 0 {
+                        return Ok(());
+                    }
+                    file.write_all(&buf[..n])?;
+                    if let Some(frames) = &frames {
+                        super::write_frame(&mut *frames.lock().unwrap(), stream_id, &buf[..n])?;
+                    }
+                }
+            });
+            Ok(Redirect { fd, saved, copier })
+        }
+
+        /// Points the descriptor back at its original target. This closes
+        /// the pipe's last write end, so the copier sees end of file.
+        pub fn restore(self) -> io::Result<()> {
+            let saved = self.saved;
+            self.replace(saved)
+        }
+
+        /// Points the descriptor at `target` instead of its original one,
+        /// ending the copier the same way as `restore`.
+        pub fn replace(self, target: RawFd) -> io::Result<()> {
+            // Safety: `saved` was duplicated in `new` and is closed only here.
+            unsafe {
+                check(libc::dup2(target, self.fd))?;
+                libc::close(self.saved);
+            }
+            self.copier
+                .join()
+                .map_err(|_| io::Error::new(io::ErrorKind::Other, "output copier panicked"))?
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_write_frame() {
+        let mut out = Vec::new();
+        write_frame(&mut out, STDERR_ID, b"abc").unwrap();
+        write_frame(&mut out, STDOUT_ID, b"").unwrap();
+        assert_eq!(out, b"\x02\0\0\0\x03abc\x01\0\0\0\0");
+    }
+}
@@ -0,0 +1,175 @@
+//! Machine-readable description of the command line, printed by
+//! `--help-json`. Built from the clap definitions, so it can't drift from
+//! what the parser accepts; `--help` and `--version` are left out.
+
+use serde::Serialize;
+use structopt::clap::{App, ArgSettings};
+
+pub const SCHEMA_VERSION: u32 = 1;
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "kebab-case")]
+pub struct CliSchema {
+    pub schema_version: u32,
+    pub runtime_version: &'static str,
+    pub command: CommandSchema,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "kebab-case")]
+pub struct CommandSchema {
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub about: Option<String>,
+    pub args: Vec<ArgSchema>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub subcommands: Vec<CommandSchema>,
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum ArgKind {
+    /// Switch without a value.
+    Flag,
+    /// `--name <value>`.
+    Option,
+    Positional,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "kebab-case")]
+pub struct ArgSchema {
+    pub name: String,
+    pub kind: ArgKind,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub long: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub short: Option<char>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub help: Option<String>,
+    pub required: bool,
+    /// Can be given more than once, or takes several values.
+    pub multiple: bool,
+    /// Accepted by every subcommand.
+    pub global: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub value_name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub default: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub possible_values: Vec<String>,
+}
+
+pub fn describe(app: &App) -> CliSchema {
+    CliSchema {
+        schema_version: SCHEMA_VERSION,
+        runtime_version: crate::RUNTIME_VERSION,
+        command: command(app),
+    }
+}
+
+fn command(app: &App) -> CommandSchema {
+    let p = &app.p;
+    let mut args = Vec::new();
+    for flag in &p.flags {
+        if flag.b.settings.is_set(ArgSettings::Hidden) {
+            continue;
+        }
+        args.push(ArgSchema {
+            name: flag.b.name.to_string(),
+            kind: ArgKind::Flag,
+            long: flag.s.long.map(str::to_string),
+            short: flag.s.short,
+            help: flag.b.help.map(str::to_string),
+            required: false,
+            multiple: flag.b.settings.is_set(ArgSettings::Multiple),
+            global: flag.b.settings.is_set(ArgSettings::Global),
+            value_name: None,
+            default: None,
+            possible_values: Vec::new(),
+        });
+    }
+    for opt in &p.opts {
+        if opt.b.settings.is_set(ArgSettings::Hidden) {
+            continue;
+        }
+        args.push(ArgSchema {
+            name: opt.b.name.to_string(),
+            kind: ArgKind::Option,
+            long: opt.s.long.map(str::to_string),
+            short: opt.s.short,
+            help: opt.b.help.map(str::to_string),
+            required: opt.b.settings.is_set(ArgSettings::Required),
+            multiple: opt.b.settings.is_set(ArgSettings::Multiple),
+            global: opt.b.settings.is_set(ArgSettings::Global),
+            value_name: opt
+                .v
+                .val_names
+                .as_ref()
+                .and_then(|names| names.values().next())
+                .map(|name| name.to_string()),
+            default: opt
+                .v
+                .default_val
+                .map(|value| value.to_string_lossy().into_owned()),
+            possible_values: possible_values(&opt.v.possible_vals),
+        });
+    }
+    for pos in p.positionals.values() {
+        args.push(ArgSchema {
+            name: pos.b.name.to_string(),
+            kind: ArgKind::Positional,
+            long: None,
+            short: None,
+            help: pos.b.help.map(str::to_string),
+            required: pos.b.settings.is_set(ArgSettings::Required),
+            multiple: pos.b.settings.is_set(ArgSettings::Multiple),
+            global: false,
+            value_name: None,
+            default: pos
+                .v
+                .default_val
+                .map(|value| value.to_string_lossy().into_owned()),
+            possible_values: possible_values(&pos.v.possible_vals),
+        });
+    }
+    CommandSchema {
+        name: p.meta.name.clone(),
+        about: p.meta.about.map(str::to_string),
+        args,
+        subcommands: p.subcommands.iter().map(command).collect(),
+    }
+}
+
+fn possible_values(values: &Option<Vec<&str>>) -> Vec<String> {
+    values
+        .iter()
+        .flatten()
+        .map(|value| value.to_string())
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use structopt::clap::{Arg, SubCommand};
+
+    #[test]
+    fn test_describe() {
+        let app = App::new("runtime").subcommand(
+            SubCommand::with_name("exec")
+                .arg(Arg::with_name("timeout").long("timeout").takes_value(true))
+                .arg(Arg::with_name("json").long("json"))
+                .arg(Arg::with_name("prog").required(true)),
+        );
+        let schema = describe(&app).command;
+        let exec = &schema.subcommands[0];
+        assert_eq!(exec.name, "exec");
+        let kinds: Vec<_> = exec.args.iter().map(|arg| &arg.kind).collect();
+        assert_eq!(
+            kinds,
+            [&ArgKind::Flag, &ArgKind::Option, &ArgKind::Positional]
+        );
+        assert!(exec.args[2].required);
+    }
+}
@@ -0,0 +1,33 @@
+use crate::report::ExecReport;
+use std::fs::{self, File};
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+
+pub const CRASH_DIR: &str = "crash";
+
+/// Bytes kept from the end of each captured output stream.
+const OUTPUT_TAIL: u64 = 64 * 1024;
+
+/// Writes `crash/<exec-id>/` with the exec report and the tails of the
+/// run's captured stdout and stderr.
+pub fn write_bundle(workdir: &Path, report: &ExecReport) -> anyhow::Result<PathBuf> {
+    let dir = workdir.join(CRASH_DIR).join(&report.exec_id);
+    fs::create_dir_all(&dir)?;
+    fs::write(dir.join("report.json"), serde_json::to_vec_pretty(report)?)?;
+    for stream in &["stdout", "stderr"] {
+        let output = workdir.join(&report.exec_id).join(stream);
+        if output.exists() {
+            fs::write(dir.join(stream), tail(&output, OUTPUT_TAIL)?)?;
+        }
+    }
+    Ok(dir)
+}
+
+pub(crate) fn tail(path: &Path, len: u64) -> io::Result<Vec<u8>> {
+    let mut file = File::open(path)?;
+    let size = file.metadata()?.len();
+    file.seek(SeekFrom::Start(size.saturating_sub(len)))?;
+    let mut data = Vec::new();
+    file.read_to_end(&mut data)?;
+    Ok(data)
+}
@@ -0,0 +1,166 @@
+use crate::image::RuntimeImage;
+use crate::preflight::FsCapabilities;
+use crate::timing;
+use anyhow::bail;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Component, Path, PathBuf};
+use std::time::UNIX_EPOCH;
+use ya_emscripten_meta::MountPoint;
+
+pub const DEPLOY_FILE: &str = "deploy.json";
+pub const MOUNTS_FILE: &str = "mounts.json";
+/// Mount directories created so far by a deploy that hasn't finished.
+pub const JOURNAL_FILE: &str = "deploy.journal.json";
+
+/// Deployment metadata stored in the workdir next to `mounts.json`.
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "kebab-case")]
+pub struct DeployInfo {
+    pub filesystem: FsCapabilities,
+    /// See `RuntimeImage::hash`, missing in deployments of older runtimes.
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub image_hash: Option<String>,
+    /// Identifies the archive `image_hash` was computed from.
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub image_stamp: Option<ImageStamp>,
+    /// Manifest id of the image, missing in deployments of older runtimes.
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub image_id: Option<String>,
+}
+
+/// Path, size and modification time of a package archive, used to tell
+/// whether a later run was given the deployed archive without rehashing it.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub struct ImageStamp {
+    pub path: PathBuf,
+    pub len: u64,
+    pub modified_ns: u64,
+}
+
+impl ImageStamp {
+    pub fn of(path: &Path) -> anyhow::Result<Self> {
+        let meta = fs::metadata(path)?;
+        let modified = meta.modified()?.duration_since(UNIX_EPOCH)?;
+        Ok(ImageStamp {
+            path: fs::canonicalize(path)?,
+            len: meta.len(),
+            modified_ns: modified.as_nanos() as u64,
+        })
+    }
+}
+
+impl DeployInfo {
+    /// Hash of `image`, taken from the deployment when it was given the
+    /// same unchanged archive.
+    pub fn hash_of(info: Option<&DeployInfo>, image: &RuntimeImage) -> anyhow::Result<String> {
+        if let Some(DeployInfo {
+            image_hash: Some(hash),
+            image_stamp: Some(stamp),
+            ..
+        }) = info
+        {
+            if image.stamp()?.as_ref() == Some(stamp) {
+                return Ok(hash.clone());
+            }
+        }
+        timing::phase("image-hash", || image.hash())
+    }
+
+    pub fn load(workdir: &Path) -> anyhow::Result<Self> {
+        let bytes = fs::read(workdir.join(DEPLOY_FILE))?;
+        Ok(serde_json::from_slice(&bytes)?)
+    }
+
+    pub fn save(&self, workdir: &Path) -> anyhow::Result<()> {
+        write_atomic(
+            &workdir.join(DEPLOY_FILE),
+            &serde_json::to_vec_pretty(self)?,
+        )?;
+        Ok(())
+    }
+}
+
+/// Version of the `mounts.json` layout. Version 1 was a bare array.
+pub const MOUNTS_LAYOUT_VERSION: u32 = 2;
+
+/// `mounts.json`. Directories are single path components relative to the
+/// workdir, so a workdir can be moved without breaking the deployment.
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "kebab-case")]
+struct MountsFile {
+    layout_version: u32,
+    mounts: Vec<(String, MountPoint)>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum AnyMountsFile {
+    Versioned(MountsFile),
+    Legacy(Vec<(String, MountPoint)>),
+}
+
+pub fn load_mounts(workdir: &Path) -> anyhow::Result<Vec<(String, MountPoint)>> {
+    let mounts =
+        match serde_json::from_slice::<AnyMountsFile>(&fs::read(workdir.join(MOUNTS_FILE))?)? {
+            AnyMountsFile::Versioned(file) if file.layout_version > MOUNTS_LAYOUT_VERSION => {
+                bail!(
+                    "unsupported mounts.json layout version {}",
+                    file.layout_version
+                )
+            }
+            AnyMountsFile::Versioned(file) => file.mounts,
+            AnyMountsFile::Legacy(mounts) => mounts,
+        };
+    for (dir, _) in &mounts {
+        let mut components = Path::new(dir).components();
+        match (components.next(), components.next()) {
+            (Some(Component::Normal(_)), None) => (),
+            _ => bail!("mount directory {:?} is not relative to the workdir", dir),
+        }
+    }
+    Ok(mounts)
+}
+
+pub fn save_mounts(workdir: &Path, mounts: Vec<(String, MountPoint)>) -> anyhow::Result<()> {
+    let file = MountsFile {
+        layout_version: MOUNTS_LAYOUT_VERSION,
+        mounts,
+    };
+    write_atomic(
+        &workdir.join(MOUNTS_FILE),
+        &serde_json::to_vec_pretty(&file)?,
+    )?;
+    Ok(())
+}
+
+pub fn load_journal(workdir: &Path) -> anyhow::Result<Vec<(String, MountPoint)>> {
+    let path = workdir.join(JOURNAL_FILE);
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    Ok(serde_json::from_slice(&fs::read(path)?)?)
+}
+
+pub fn save_journal(workdir: &Path, mounts: &[(String, MountPoint)]) -> anyhow::Result<()> {
+    write_atomic(&workdir.join(JOURNAL_FILE), &serde_json::to_vec(mounts)?)?;
+    Ok(())
+}
+
+/// Replaces `path` with `bytes` so readers see either the old or the new
+/// content, never a partially written file.
+pub(crate) fn write_atomic(path: &Path, bytes: &[u8]) -> io::Result<()> {
+    let mut tmp_name = path.as_os_str().to_owned();
+    tmp_name.push(".tmp");
+    let tmp_path = Path::new(&tmp_name);
+
+    let mut file = fs::File::create(tmp_path)?;
+    file.write_all(bytes)?;
+    file.sync_all()?;
+    fs::rename(tmp_path, path)
+}
@@ -0,0 +1,59 @@
+use std::fmt::Write;
+use ya_emscripten_meta::{EntryPoint, Manifest, MountPoint};
+
+/// Renders a human readable description of an image.
+pub fn markdown(m: &Manifest) -> String {
+    let mut out = String::new();
+    let _ = writeln!(out, "# {}\n", m.name);
+    let _ = writeln!(out, "Image id: `{}`\n", m.id);
+    let _ = writeln!(out, "Runtime: {:?}\n", m.runtime);
+    if let Some(work_dir) = &m.work_dir {
+        let _ = writeln!(out, "Working directory: `{}`\n", work_dir);
+    }
+
+    if let Some(main) = &m.main {
+        let _ = writeln!(out, "## Main entry point\n");
+        entry_point(&mut out, main);
+    }
+    if !m.entry_points.is_empty() {
+        let _ = writeln!(out, "## Entry points\n");
+        for ep in &m.entry_points {
+            entry_point(&mut out, ep);
+        }
+    }
+
+    if !m.mount_points.is_empty() {
+        let _ = writeln!(out, "## Mounts\n");
+        let _ = writeln!(out, "| Path | Access |");
+        let _ = writeln!(out, "|------|--------|");
+        for mount_point in &m.mount_points {
+            let access = match mount_point {
+                MountPoint::Ro(_) => "read-only",
+                MountPoint::Rw(_) => "read-write",
+                MountPoint::Wo(_) => "write-only",
+            };
+            let _ = writeln!(out, "| `{}` | {} |", mount_point.path(), access);
+        }
+        out.push('\n');
+    }
+    out
+}
+
+fn entry_point(out: &mut String, ep: &EntryPoint) {
+    let _ = writeln!(out, "### `{}`\n", ep.id);
+    let _ = writeln!(out, "- wasm: `{}`", ep.wasm_path);
+    if !ep.args_prefix.is_empty() {
+        let _ = writeln!(out, "- fixed arguments: `{}`", ep.args_prefix.join(" "));
+    }
+    if let Some(stack_size) = ep.stack_size {
+        let _ = writeln!(out, "- stack size: {} bytes", stack_size);
+    }
+    for (key, value) in &ep.env {
+        let _ = writeln!(out, "- environment: `{}={}`", key, value);
+    }
+    if !ep.success_codes.is_empty() {
+        let codes: Vec<String> = ep.success_codes.iter().map(i32::to_string).collect();
+        let _ = writeln!(out, "- successful exit codes: 0, {}", codes.join(", "));
+    }
+    out.push('\n');
+}
@@ -0,0 +1,71 @@
+//! Stable diagnostic lines on stderr for supervisors that only scrape it.
+//!
+//! Format: `RUNTIME-EVENT v<version> <event> key="value" ...`, one per line,
+//! printed regardless of any other output settings. Event names and keys
+//! are part of the protocol; add new ones, don't rename existing ones.
+//!
+//! With `connect_socket` the same events are also written to a unix socket
+//! as JSON lines: `{"version":1,"event":"...","fields":{"key":"value"}}`.
+
+use crate::capture::RuntimeStderr;
+use lazy_static::lazy_static;
+use log::warn;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io::{self, Write};
+use std::path::Path;
+use std::sync::Mutex;
+
+pub const PROTOCOL_VERSION: u32 = 1;
+
+#[cfg(unix)]
+type Socket = std::os::unix::net::UnixStream;
+#[cfg(not(unix))]
+type Socket = std::fs::File;
+
+lazy_static! {
+    static ref SOCKET: Mutex<Option<Socket>> = Mutex::new(None);
+}
+
+pub fn emit(event: &str, fields: &[(&str, &dyn fmt::Display)]) {
+    let mut line = format!("RUNTIME-EVENT v{} {}", PROTOCOL_VERSION, event);
+    for (key, value) in fields {
+        line.push_str(&format!(" {}={:?}", key, value.to_string()));
+    }
+    line.push('\n');
+    let _ = RuntimeStderr.write_all(line.as_bytes());
+
+    let mut socket = SOCKET.lock().unwrap();
+    if let Some(stream) = socket.as_mut() {
+        let fields: BTreeMap<_, _> = fields
+            .iter()
+            .map(|(key, value)| (*key, value.to_string()))
+            .collect();
+        let mut json = serde_json::json!({
+            "version": PROTOCOL_VERSION,
+            "event": event,
+            "fields": fields,
+        })
+        .to_string();
+        json.push('\n');
+        if let Err(e) = stream.write_all(json.as_bytes()) {
+            warn!("failed to write to the events socket, disconnecting: {}", e);
+            *socket = None;
+        }
+    }
+}
+
+/// Connects to a unix socket that receives every following event.
+#[cfg(unix)]
+pub fn connect_socket(path: &Path) -> io::Result<()> {
+    *SOCKET.lock().unwrap() = Some(Socket::connect(path)?);
+    Ok(())
+}
+
+#[cfg(not(unix))]
+pub fn connect_socket(_path: &Path) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Other,
+        "events sockets are only supported on unix",
+    ))
+}
@@ -0,0 +1,63 @@
+//! Limit on concurrent runs against one workdir. Every run is its own
+//! process, so a run holds one of `max` lock files in `<workdir>/.exec-slots`
+//! for its whole duration; the lock is released when the process exits.
+
+use crate::events;
+use std::fs::File;
+use std::io;
+use std::path::Path;
+use std::thread;
+use std::time::Duration;
+
+pub const SLOTS_DIR: &str = ".exec-slots";
+
+/// How often a queued run retries to get a slot.
+const RETRY_INTERVAL: Duration = Duration::from_millis(100);
+
+pub struct ExecSlot {
+    _file: File,
+}
+
+impl ExecSlot {
+    /// Waits until fewer than `max` runs hold a slot, then takes one.
+    pub fn acquire(workdir: &Path, max: usize) -> io::Result<Self> {
+        let dir = workdir.join(SLOTS_DIR);
+        std::fs::create_dir_all(&dir)?;
+        let mut queued = false;
+        loop {
+            for i in 0..max.max(1) {
+                let file = File::create(dir.join(format!("{}.lock", i)))?;
+                if try_lock(&file)? {
+                    return Ok(ExecSlot { _file: file });
+                }
+            }
+            if !queued {
+                events::emit("exec.queued", &[("max-concurrent", &max)]);
+                queued = true;
+            }
+            thread::sleep(RETRY_INTERVAL);
+        }
+    }
+}
+
+#[cfg(unix)]
+fn try_lock(file: &File) -> io::Result<bool> {
+    use std::os::unix::io::AsRawFd;
+
+    // Safety: flock only operates on the descriptor, which `file` owns.
+    if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) } == 0 {
+        return Ok(true);
+    }
+    let e = io::Error::last_os_error();
+    if e.raw_os_error() == Some(libc::EWOULDBLOCK) {
+        Ok(false)
+    } else {
+        Err(e)
+    }
+}
+
+#[cfg(not(unix))]
+fn try_lock(_file: &File) -> io::Result<bool> {
+    log::warn!("--max-concurrent is only supported on unix");
+    Ok(true)
+}
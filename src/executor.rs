@@ -0,0 +1,660 @@
+use crate::capture::OutputCapture;
+use crate::deploy::DeployInfo;
+use crate::exec_slots::ExecSlot;
+use crate::home::{HomeDir, EXIT_CODE_FILE, HOME_DIR, STARTED_FILE};
+use crate::host_paths::{self, HostPathArgs};
+use crate::image::RuntimeImage;
+use crate::mounts::{self, MountActivity};
+use crate::package::DataPackage;
+use crate::priority::Priority;
+use crate::report::{ErrorClass, ExecReport, STATUS_OUTPUT_TAIL};
+use crate::runtime_info::{RuntimeInfo, RUNTIME_DIR, STDIN_FILE};
+use crate::scan::{ScanResult, Scanner};
+use crate::spec::ExtraMount;
+use crate::usage::CounterCommand;
+use crate::wasm_features::WasmFeature;
+use crate::workdir::{host_path, RunOutput, Workdir};
+use crate::{
+    crash, deploy, events, extract_cache, glue, health, memory_limit, normalize_path, prelude,
+    stats, strict, timing, volumes, wasm_features,
+};
+use anyhow::{bail, Context};
+use log::{debug, info, warn};
+use sp_wasm_engine::prelude::NodeMode;
+use sp_wasm_engine::sandbox::load::Bytes;
+use sp_wasm_engine::sandbox::Sandbox;
+use std::any::Any;
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+use ya_emscripten_meta::{EntryPoint, MountPoint, PACKAGE_MOUNT};
+
+/// Native stack size of the engine thread when neither the command line
+/// nor the entry point asks for one. Matches the usual Linux main thread limit.
+pub const DEFAULT_STACK_SIZE: usize = 8 * 1024 * 1024;
+
+/// Settings of a single run.
+#[derive(Debug, Clone)]
+pub struct RunOptions {
+    pub args: Vec<String>,
+    /// Extra environment variables, on top of the entry point's `env`,
+    /// `HOME` and `XDG_*`.
+    pub env: BTreeMap<String, String>,
+    /// Overrides the entry point's stack size.
+    pub stack_size: Option<usize>,
+    /// Overrides the entry point's timeout.
+    pub timeout: Option<Duration>,
+    /// Limit on the time until `main` is called, covering image loading,
+    /// glue evaluation, module instantiation and static constructors.
+    pub startup_timeout: Option<Duration>,
+    /// Scheduling class of the engine thread.
+    pub priority: Priority,
+    /// Maximum linear memory of the program in bytes.
+    pub mem_limit: Option<u64>,
+    /// Maximum total size of the writable mounts in bytes.
+    pub output_quota: Option<u64>,
+    /// Maximum number of files and directories in the writable mounts.
+    pub max_files: Option<u64>,
+    /// Flush writable mounts to disk after the run.
+    pub fsync: bool,
+    pub data_packages: Vec<DataPackage>,
+    pub run_outputs: Vec<RunOutput>,
+    /// Host directories from the spec.
+    pub extra_mounts: Vec<ExtraMount>,
+    /// Recorded in the exec report.
+    pub args_sha256: Option<String>,
+    /// Scans inputs before and writable mounts after the run.
+    pub scanner: Option<Scanner>,
+    /// Hooks adding custom counters to the report's usage, see `usage`.
+    pub usage_counters: Vec<CounterCommand>,
+    /// Write a `crash/` bundle when the engine fails.
+    pub crash_dump: bool,
+    /// Forward the program's output as frames on stdout while it runs,
+    /// see `capture`.
+    pub stream_output: bool,
+    /// File fed to the program's standard input, `-` for the runtime's own.
+    pub stdin: Option<PathBuf>,
+    /// Handling of arguments that look like host paths.
+    pub host_path_args: HostPathArgs,
+    /// Waits while this many other runs use the workdir.
+    pub max_concurrent: Option<usize>,
+}
+
+impl Default for RunOptions {
+    fn default() -> Self {
+        RunOptions {
+            args: Vec::new(),
+            env: BTreeMap::new(),
+            stack_size: None,
+            timeout: None,
+            startup_timeout: None,
+            priority: Priority::Normal,
+            mem_limit: None,
+            output_quota: None,
+            max_files: None,
+            fsync: true,
+            data_packages: Vec::new(),
+            run_outputs: Vec::new(),
+            extra_mounts: Vec::new(),
+            args_sha256: None,
+            scanner: None,
+            usage_counters: Vec::new(),
+            crash_dump: false,
+            stream_output: false,
+            stdin: None,
+            host_path_args: HostPathArgs::Warn,
+            max_concurrent: None,
+        }
+    }
+}
+
+/// Runs entry points of a deployed image.
+pub struct Executor<'a> {
+    image: &'a RuntimeImage,
+    workdir: &'a Workdir,
+}
+
+impl<'a> Executor<'a> {
+    pub fn new(image: &'a RuntimeImage, workdir: &'a Workdir) -> Self {
+        Executor { image, workdir }
+    }
+
+    /// Runs entry point on a dedicated engine thread, so deeply recursive
+    /// programs can be given more native stack than the main thread has.
+    /// Every run leaves an exec report in the workdir, and the program's
+    /// stdout and stderr in `<exec-id>/`, so several processes can run
+    /// entry points of one deployment at the same time.
+    ///
+    /// The engine can't be interrupted: after a timeout, or once the output
+    /// quota or file count limit is exceeded, the engine thread keeps
+    /// running until the process exits.
+    ///
+    /// Returns the exec report of a successful run.
+    pub fn run(&self, ep: &EntryPoint, opts: RunOptions) -> anyhow::Result<ExecReport> {
+        let _slot = match opts.max_concurrent {
+            Some(max) => Some(ExecSlot::acquire(self.workdir.path(), max)?),
+            None => None,
+        };
+        let stack_size = opts
+            .stack_size
+            .or(ep.stack_size)
+            .unwrap_or(DEFAULT_STACK_SIZE);
+        debug!("engine stack size: {} bytes", stack_size);
+
+        let exec_id = uuid::Uuid::new_v4().to_hyphenated().to_string();
+        let args_sha256 = opts.args_sha256.clone();
+        let crash_dump = opts.crash_dump;
+        let scanner = opts.scanner.clone();
+        let usage_counters = opts.usage_counters.clone();
+        let started = Instant::now();
+        let started_at = SystemTime::now();
+        let cpu_before = stats::cpu_time();
+        let mut scans = Vec::new();
+        let result = match &scanner {
+            Some(scanner) => self.scan_inputs(scanner, &opts.data_packages, &mut scans),
+            None => Ok(()),
+        }
+        .and_then(|()| self.supervise(ep, &exec_id, stack_size, opts));
+        if let Some(scanner) = &scanner {
+            let mounts = self.workdir.mounts().unwrap_or_else(|e| {
+                warn!("failed to list mounts to scan: {}", e);
+                Vec::new()
+            });
+            for (dir, mount_point) in mounts {
+                if matches!(mount_point, MountPoint::Ro(_)) {
+                    continue;
+                }
+                match scanner.scan("output", &self.workdir.path().join(dir)) {
+                    Ok(scan) => scans.push(scan),
+                    Err(e) => warn!("failed to scan {}: {}", mount_point.path(), e),
+                }
+            }
+        }
+
+        let duration = started.elapsed();
+        let cpu_time = stats::cpu_time().saturating_sub(cpu_before);
+
+        let record = stats::RunRecord {
+            entry_point: ep.id.clone(),
+            success: result.is_ok(),
+            duration_ms: duration.as_millis() as u64,
+            max_rss_kb: stats::max_rss_kb(),
+            output_bytes: self.workdir.output_bytes().unwrap_or_else(|e| {
+                warn!("failed to measure output: {}", e);
+                0
+            }),
+        };
+        if let Err(e) = stats::record(&self.image.manifest().id, &record) {
+            warn!("failed to record run stats: {}", e);
+        }
+
+        let mut report = ExecReport::new(exec_id, ep.id.clone(), stack_size, started_at, &result);
+        report.args_sha256 = args_sha256;
+        report.scans = scans;
+        let exec_dir = self.workdir.path().join(&report.exec_id);
+        report.status.stdout =
+            crash::tail(&exec_dir.join("stdout"), STATUS_OUTPUT_TAIL).unwrap_or_default();
+        report.status.stderr =
+            crash::tail(&exec_dir.join("stderr"), STATUS_OUTPUT_TAIL).unwrap_or_default();
+        let usage = [
+            ("golem.usage.duration_sec", duration.as_secs_f64()),
+            ("golem.usage.cpu_sec", cpu_time.as_secs_f64()),
+            ("golem.usage.max_rss_kb", record.max_rss_kb as f64),
+        ];
+        for (counter, value) in usage.iter() {
+            report.usage.insert(counter.to_string(), *value);
+        }
+        for command in &usage_counters {
+            let counters = serde_json::to_vec(&report)
+                .map_err(anyhow::Error::from)
+                .and_then(|json| command.collect(&exec_dir, &json));
+            match counters {
+                Ok(counters) => {
+                    for (counter, value) in counters {
+                        if report.usage.contains_key(&counter) {
+                            warn!("usage counter {} is already set, ignoring it", counter);
+                            continue;
+                        }
+                        report.usage.insert(counter, value);
+                    }
+                }
+                Err(e) => warn!("failed to collect usage counters: {}", e),
+            }
+        }
+        let path = report.save(self.workdir.path())?;
+        info!("exec report: {}", path.display());
+        let crashed = match &result {
+            Err(e) => ErrorClass::of(e) == Some(ErrorClass::Engine),
+            Ok(_) => false,
+        };
+        if crash_dump && crashed {
+            match crash::write_bundle(self.workdir.path(), &report) {
+                Ok(dir) => info!("crash bundle: {}", dir.display()),
+                Err(e) => warn!("failed to write crash bundle: {}", e),
+            }
+        }
+        result.map(|_| report)
+    }
+
+    fn scan_inputs(
+        &self,
+        scanner: &Scanner,
+        data_packages: &[DataPackage],
+        scans: &mut Vec<ScanResult>,
+    ) -> anyhow::Result<()> {
+        let inputs = std::iter::once(self.image.package())
+            .chain(data_packages.iter().map(|data| &data.package));
+        for package in inputs {
+            let scan = scanner.scan("input", package.path())?;
+            let clean = scan.clean;
+            scans.push(scan);
+            if !clean {
+                return Err(
+                    anyhow::anyhow!("scanner flagged {}", package.path().display())
+                        .context(ErrorClass::BadImage),
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// Runs the engine thread and waits for it, within the timeout, output
+    /// quota and file count limit of `opts`.
+    fn supervise(
+        &self,
+        ep: &EntryPoint,
+        exec_id: &str,
+        stack_size: usize,
+        mut opts: RunOptions,
+    ) -> anyhow::Result<i32> {
+        let timeout = opts.timeout.or_else(|| ep.timeout.map(Duration::from_secs));
+        let startup_timeout = opts.startup_timeout;
+        let output_quota = opts.output_quota;
+        let max_files = opts.max_files;
+
+        let mut info = RuntimeInfo::new(
+            exec_id,
+            &ep.id,
+            timeout.map(|timeout| SystemTime::now() + timeout),
+        );
+        info.limits.timeout_sec = timeout.map(|timeout| timeout.as_secs());
+        info.limits.mem_limit = opts.mem_limit;
+        info.limits.output_quota = output_quota;
+        info.limits.max_files = max_files;
+        info.limits.stack_size = stack_size;
+        info.add_mount(&MountPoint::Ro(PACKAGE_MOUNT.to_string()));
+        for data in &opts.data_packages {
+            info.add_mount(&MountPoint::Ro(data.guest_path.clone()));
+        }
+        for output in &opts.run_outputs {
+            self.workdir.run_output_dir(&output.exec_id)?;
+            info.add_mount(&MountPoint::Ro(output.guest_path.clone()));
+        }
+        for (_, mount_point) in self.workdir.mounts()? {
+            info.add_mount(&mount_point);
+        }
+        for mount in &opts.extra_mounts {
+            info.add_mount(&extra_mount_point(mount));
+        }
+        for volume in &self.image.manifest().volumes {
+            info.add_mount(&MountPoint::Rw(volume.path.clone()));
+        }
+        info.add_mount(&MountPoint::Rw(HOME_DIR.to_string()));
+        info.add_mount(&MountPoint::Ro(RUNTIME_DIR.to_string()));
+        host_paths::check(
+            &opts.args,
+            info.mounts.keys().map(String::as_str),
+            opts.host_path_args,
+        )?;
+
+        for (key, value) in &ep.env {
+            opts.env.entry(key.clone()).or_insert_with(|| value.clone());
+        }
+        let home = HomeDir::create(self.workdir.path())?;
+        home.set_env_defaults(&mut opts.env);
+        let capture = CaptureSlot::default();
+
+        let (done, result) = mpsc::channel();
+        {
+            let image = self.image.clone();
+            let workdir = self.workdir.clone();
+            let ep = ep.clone();
+            let home = home.path().to_owned();
+            let capture = capture.clone();
+
+            thread::Builder::new()
+                .name("engine".into())
+                .stack_size(stack_size)
+                .spawn(move || {
+                    if opts.priority != Priority::Normal {
+                        opts.priority.apply_to_current_thread();
+                    }
+                    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+                        run_ep(&image, &workdir, &ep, &info, &home, &capture, opts)
+                    }))
+                    .unwrap_or_else(|payload| Err(engine_panic(payload)));
+                    let _ = done.send(result);
+                })?;
+        }
+        let panicked = || anyhow::anyhow!("engine thread panicked").context(ErrorClass::Engine);
+        let deadline = timeout.map(|timeout| Instant::now() + timeout);
+        let mut startup_deadline = startup_timeout.map(|timeout| Instant::now() + timeout);
+        let started_file = home.path().join(STARTED_FILE);
+        let abort = |event: &str, e: anyhow::Error| -> anyhow::Result<i32> {
+            abandon_capture(&capture)?;
+            events::emit(event, &[("entry-point", &ep.id)]);
+            Err(e)
+        };
+        let result = loop {
+            let polls = [
+                deadline.map(|deadline| deadline.saturating_duration_since(Instant::now())),
+                output_quota.or(max_files).map(|_| QUOTA_POLL_INTERVAL),
+                startup_deadline.map(|_| STARTUP_POLL_INTERVAL),
+            ];
+            let wait = match polls.iter().flatten().min() {
+                Some(wait) => *wait,
+                None => break result.recv().unwrap_or_else(|_| Err(panicked())),
+            };
+            match result.recv_timeout(wait) {
+                Ok(result) => break result,
+                Err(RecvTimeoutError::Disconnected) => break Err(panicked()),
+                Err(RecvTimeoutError::Timeout) => (),
+            }
+            if let (Some(timeout), Some(startup)) = (startup_timeout, startup_deadline) {
+                if started_file.exists() {
+                    startup_deadline = None;
+                } else if Instant::now() >= startup {
+                    break abort(
+                        "exec.startup-timeout",
+                        anyhow::anyhow!("entry point {} didn't start within {:?}", ep.id, timeout)
+                            .context(ErrorClass::StartupTimeout),
+                    );
+                }
+            }
+            if let (Some(timeout), Some(deadline)) = (timeout, deadline) {
+                if Instant::now() >= deadline {
+                    break abort(
+                        "exec.timeout",
+                        anyhow::anyhow!("entry point {} timed out after {:?}", ep.id, timeout)
+                            .context(ErrorClass::Timeout),
+                    );
+                }
+            }
+            if let Some(quota) = output_quota {
+                // Files may disappear mid-walk while the program runs.
+                let written = self.workdir.output_bytes().unwrap_or(0);
+                if written > quota {
+                    break abort("exec.quota-exceeded", quota_exceeded(quota));
+                }
+            }
+            if let Some(limit) = max_files {
+                if let Ok((count, dirs)) = self.workdir.output_entries(TOP_DIRS) {
+                    if count > limit {
+                        let e = file_count_exceeded(limit, &dirs);
+                        break abort("exec.file-count-exceeded", e);
+                    }
+                }
+            }
+        };
+        let result = match output_quota {
+            Some(quota) if result.is_ok() && self.workdir.output_bytes()? > quota => {
+                Err(quota_exceeded(quota))
+            }
+            _ => result,
+        };
+        let result = match max_files {
+            Some(limit) if result.is_ok() => {
+                let (count, dirs) = self.workdir.output_entries(TOP_DIRS)?;
+                if count > limit {
+                    Err(file_count_exceeded(limit, &dirs))
+                } else {
+                    result
+                }
+            }
+            _ => result,
+        };
+        drop(home);
+        result
+    }
+}
+
+fn extra_mount_point(mount: &ExtraMount) -> MountPoint {
+    if mount.writable {
+        MountPoint::Rw(mount.guest_path.clone())
+    } else {
+        MountPoint::Ro(mount.guest_path.clone())
+    }
+}
+
+/// Error for a panic on the engine thread. The thread's guards, like the
+/// output capture, are released by unwinding.
+fn engine_panic(payload: Box<dyn Any + Send>) -> anyhow::Error {
+    let message = match payload.downcast_ref::<&str>() {
+        Some(message) => message.to_string(),
+        None => payload
+            .downcast_ref::<String>()
+            .cloned()
+            .unwrap_or_else(|| "unknown cause".to_string()),
+    };
+    anyhow::anyhow!("engine thread panicked: {}", message).context(ErrorClass::Engine)
+}
+
+/// How often the writable mounts are measured against the output quota.
+const QUOTA_POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+/// How often the home directory is checked for `STARTED_FILE`.
+const STARTUP_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Directories listed when the file count limit is exceeded.
+const TOP_DIRS: usize = 5;
+
+fn file_count_exceeded(limit: u64, dirs: &[(String, u64)]) -> anyhow::Error {
+    let dirs: Vec<String> = dirs
+        .iter()
+        .map(|(dir, count)| format!("{} ({})", dir, count))
+        .collect();
+    anyhow::anyhow!(
+        "writable mounts hold more than {} files and directories, most in: {}",
+        limit,
+        dirs.join(", ")
+    )
+    .context(ErrorClass::FileCountExceeded)
+}
+
+fn quota_exceeded(quota: u64) -> anyhow::Error {
+    anyhow::anyhow!("writable mounts exceed the output quota of {} bytes", quota)
+        .context(ErrorClass::OutputQuotaExceeded)
+}
+
+/// Output capture of a run, shared with the waiting thread so it can be
+/// ended on timeout.
+type CaptureSlot = Arc<Mutex<Option<OutputCapture>>>;
+
+fn finish_capture(capture: &CaptureSlot) -> anyhow::Result<()> {
+    let capture = capture.lock().unwrap().take();
+    if let Some(capture) = capture {
+        capture.finish()?;
+    }
+    Ok(())
+}
+
+/// Ends the capture of a run the engine thread is still executing, see
+/// `OutputCapture::abandon`.
+fn abandon_capture(capture: &CaptureSlot) -> anyhow::Result<()> {
+    let capture = capture.lock().unwrap().take();
+    if let Some(capture) = capture {
+        capture.abandon()?;
+    }
+    Ok(())
+}
+
+fn run_ep(
+    image: &RuntimeImage,
+    workdir: &Workdir,
+    ep: &EntryPoint,
+    info: &RuntimeInfo,
+    home: &Path,
+    capture: &CaptureSlot,
+    opts: RunOptions,
+) -> anyhow::Result<i32> {
+    let package = image.package();
+    let m = image.manifest();
+    let workdir = workdir.path();
+    let wasm_path = normalize_path(&ep.wasm_path)?;
+    let js_path = wasm_path.with_extension("js");
+
+    debug!("js={}, wasm={}", js_path.display(), wasm_path.display());
+
+    // The cache holds the code of the deployed image, which needn't be the
+    // one the run was given.
+    let deployed = DeployInfo::load(workdir).ok();
+    let image_hash = DeployInfo::hash_of(deployed.as_ref(), image)?;
+    let cache_key = deployed
+        .and_then(|info| info.image_hash)
+        .filter(|deployed| *deployed == image_hash);
+    let mut wasm = extract_cache::read_entry(package, cache_key.as_deref(), &wasm_path)?;
+    if let Some(limit) = opts.mem_limit {
+        wasm = memory_limit::limit_memory(&wasm, limit)?;
+    }
+    let wasm_bytes = Bytes::from_reader(wasm.as_slice())?;
+
+    let mut js = prelude::env_prelude(&opts.env).into_bytes();
+    let exit_code_path = format!("{}/{}", HOME_DIR, EXIT_CODE_FILE);
+    js.extend(prelude::exit_code_prelude(&exit_code_path).into_bytes());
+    if opts.startup_timeout.is_some() {
+        let started_path = format!("{}/{}", HOME_DIR, STARTED_FILE);
+        js.extend(prelude::started_prelude(&started_path).into_bytes());
+    }
+    if opts.stdin.is_some() {
+        let guest_path = format!("{}/{}", RUNTIME_DIR, STDIN_FILE);
+        js.extend(prelude::stdin_prelude(&guest_path).into_bytes());
+    }
+    let glue = extract_cache::read_entry(package, cache_key.as_deref(), &js_path)?;
+    let threads = glue::uses_pthreads(&String::from_utf8_lossy(&glue))
+        || wasm_features::required_features(&wasm)
+            .is_ok_and(|features| features.contains(&WasmFeature::Threads));
+    if threads {
+        return Err(
+            anyhow::anyhow!("entry point {}: {}", ep.id, glue::PTHREADS_UNSUPPORTED)
+                .context(ErrorClass::BadImage),
+        );
+    }
+    match String::from_utf8(glue) {
+        Ok(glue) => js.extend(glue::to_script(&glue).into_bytes()),
+        Err(e) => {
+            strict::fallback(
+                "non-utf8-glue",
+                format!("{} is not valid UTF-8", js_path.display()),
+            )?;
+            js.extend(e.into_bytes())
+        }
+    }
+    let js_bytes = Bytes::from_reader(js.as_slice())?;
+
+    let mut sb = Sandbox::new().map_err(anyhow::Error::msg)?;
+
+    if let Some(work_dir) = &m.work_dir {
+        sb = sb.work_dir(work_dir).map_err(anyhow::Error::msg)?;
+    }
+    let mounts = deploy::load_mounts(workdir)?;
+    sb = sb.set_exec_args(opts.args).map_err(anyhow::Error::msg)?;
+    timing::phase("engine-init", || sb.init())
+        .map_err(anyhow::Error::msg)
+        .context(ErrorClass::Engine)?;
+    sb.mount(package.path(), PACKAGE_MOUNT, NodeMode::Ro)?;
+    for data in &opts.data_packages {
+        sb.mount(data.package.path(), &data.guest_path, NodeMode::Ro)?;
+    }
+    for output in &opts.run_outputs {
+        sb.mount(
+            workdir.join(&output.exec_id),
+            &output.guest_path,
+            NodeMode::Ro,
+        )?;
+    }
+
+    for (path, mount_point) in &mounts {
+        sb.mount(workdir.join(path), mount_point.path(), NodeMode::Rw)?;
+    }
+    for mount in &opts.extra_mounts {
+        let mode = if mount.writable {
+            NodeMode::Rw
+        } else {
+            NodeMode::Ro
+        };
+        sb.mount(&mount.host_path, &mount.guest_path, mode)?;
+    }
+    let mut volume_dirs = Vec::new();
+    for volume in &m.volumes {
+        let dir = volumes::open(&image_hash, volume)?;
+        sb.mount(&dir, &volume.path, NodeMode::Rw)?;
+        volume_dirs.push((dir, volume));
+    }
+    sb.mount(home, HOME_DIR, NodeMode::Rw)?;
+    let exec_dir = workdir.join(&info.exec_id);
+    let runtime_dir = info.write(&exec_dir)?;
+    match &opts.stdin {
+        Some(path) if path == Path::new("-") => {
+            let mut file = fs::File::create(runtime_dir.join(STDIN_FILE))?;
+            io::copy(&mut io::stdin(), &mut file)?;
+        }
+        Some(path) => {
+            fs::copy(path, runtime_dir.join(STDIN_FILE))?;
+        }
+        None => (),
+    }
+    sb.mount(runtime_dir, RUNTIME_DIR, NodeMode::Ro)?;
+
+    let activity = MountActivity::snapshot(workdir, &mounts)?;
+    let _health = match &ep.health {
+        Some(probe) => match host_path(workdir, &mounts, &probe.file)? {
+            Some(host_file) => Some(health::HealthMonitor::start(probe, host_file)),
+            None => bail!("health probe file {} is outside of mounts", probe.file),
+        },
+        None => None,
+    };
+    events::emit("exec.start", &[("entry-point", &ep.id)]);
+    *capture.lock().unwrap() = Some(OutputCapture::start(&exec_dir, opts.stream_output)?);
+    let run_result = sb.run(js_bytes, wasm_bytes);
+    finish_capture(capture)?;
+    let _ = run_result
+        .map_err(anyhow::Error::msg)
+        .context(ErrorClass::Engine)?;
+    if opts.fsync {
+        for dir in activity.writable_dirs() {
+            mounts::sync_tree(&dir)?;
+        }
+    }
+    for (dir, volume) in &volume_dirs {
+        volumes::check_quota(dir, volume)?;
+    }
+    events::emit("exec.done", &[("entry-point", &ep.id)]);
+
+    // Advisory only, a run that got this far succeeded.
+    match activity.warnings() {
+        Ok(warnings) => {
+            for warning in warnings {
+                warn!("{}", serde_json::to_string(&warning)?);
+            }
+        }
+        Err(e) => warn!("failed to check mount activity: {}", e),
+    }
+
+    // Missing when the program didn't exit through emscripten's runtime.
+    let exit_code = fs::read_to_string(home.join(EXIT_CODE_FILE))
+        .ok()
+        .and_then(|code| code.trim().parse::<i32>().ok());
+    match exit_code {
+        Some(code) if code != 0 && !ep.success_codes.contains(&code) => {
+            Err(anyhow::anyhow!("entry point {} failed", ep.id)
+                .context(ErrorClass::ProgramExit(code)))
+        }
+        code => Ok(code.unwrap_or(0)),
+    }
+}
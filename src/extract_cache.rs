@@ -0,0 +1,126 @@
+//! Entry point `.js` and `.wasm` files extracted at deploy time, so runs
+//! don't have to decompress them again. Entries are keyed by image hash
+//! and evicted least recently used first once the cache outgrows
+//! `YA_EMSCRIPTEN_EXTRACT_CACHE_SIZE` bytes (default 1 GiB).
+
+use crate::deploy::write_atomic;
+use crate::image::RuntimeImage;
+use crate::package::Package;
+use crate::{mounts, normalize_path};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+const DEFAULT_MAX_SIZE: u64 = 1 << 30;
+const MAX_SIZE_ENV: &str = "YA_EMSCRIPTEN_EXTRACT_CACHE_SIZE";
+
+/// Rewritten on every use, its mtime orders entries for eviction.
+const LAST_USED: &str = ".last-used";
+
+fn cache_dir() -> Option<PathBuf> {
+    Some(
+        dirs::cache_dir()?
+            .join("ya-runtime-emscripten")
+            .join("extracted"),
+    )
+}
+
+/// Extracts the glue and module of every entry point of an archive image.
+/// Unpacked images are read directly and aren't cached.
+pub fn populate(image: &RuntimeImage, image_hash: &str) -> anyhow::Result<()> {
+    let (cache, package) = match (cache_dir(), image.package()) {
+        (Some(cache), package @ Package::Archive(_)) => (cache, package),
+        _ => return Ok(()),
+    };
+    let dir = cache.join(image_hash);
+    let m = image.manifest();
+    for ep in m.main.iter().chain(&m.entry_points) {
+        let wasm_path = normalize_path(&ep.wasm_path)?;
+        for name in [wasm_path.with_extension("js"), wasm_path].iter() {
+            let path = dir.join(name);
+            if path.is_file() {
+                continue;
+            }
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            write_atomic(&path, &package.read_entry(name)?)?;
+        }
+    }
+    fs::write(dir.join(LAST_USED), b"")?;
+    evict(&cache, &dir, max_size())?;
+    Ok(())
+}
+
+/// Reads `name` from the cache, falling back to the package.
+pub fn read_entry(
+    package: &Package,
+    image_hash: Option<&str>,
+    name: &Path,
+) -> anyhow::Result<Vec<u8>> {
+    if let (Some(cache), Some(hash)) = (cache_dir(), image_hash) {
+        let dir = cache.join(hash);
+        if let Ok(bytes) = fs::read(dir.join(name)) {
+            let _ = fs::write(dir.join(LAST_USED), b"");
+            return Ok(bytes);
+        }
+    }
+    package.read_entry(name)
+}
+
+fn max_size() -> u64 {
+    std::env::var(MAX_SIZE_ENV)
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(DEFAULT_MAX_SIZE)
+}
+
+/// Removes least recently used entries other than `keep` until the cache
+/// fits in `max_size`.
+fn evict(cache: &Path, keep: &Path, max_size: u64) -> io::Result<()> {
+    let mut entries = Vec::new();
+    let mut total = 0;
+    for entry in fs::read_dir(cache)? {
+        let path = entry?.path();
+        let size = mounts::tree_size(&path)?;
+        total += size;
+        if path != keep {
+            let used = fs::metadata(path.join(LAST_USED))
+                .and_then(|meta| meta.modified())
+                .unwrap_or(SystemTime::UNIX_EPOCH);
+            entries.push((used, size, path));
+        }
+    }
+    entries.sort();
+    for (_, size, path) in entries {
+        if total <= max_size {
+            break;
+        }
+        fs::remove_dir_all(&path)?;
+        total -= size;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_read_entry_fallback() {
+        let root = std::env::temp_dir().join(format!("extract-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&root).unwrap();
+        fs::write(root.join("ls.wasm"), b"wasm").unwrap();
+        let package = Package::Directory(root.clone());
+
+        // Nothing is cached under a fresh hash.
+        let hash = uuid::Uuid::new_v4().to_simple().to_string();
+        let name = Path::new("ls.wasm");
+        assert_eq!(read_entry(&package, Some(&hash), name).unwrap(), b"wasm");
+        assert_eq!(read_entry(&package, None, name).unwrap(), b"wasm");
+        assert!(read_entry(&package, Some(&hash), Path::new("ls.js")).is_err());
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
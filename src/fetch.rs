@@ -0,0 +1,148 @@
+//! Task packages given by URL, downloaded into a per-user cache.
+
+use crate::report::ErrorClass;
+use crate::timing;
+use anyhow::{anyhow, bail, Context};
+use log::info;
+use sha2::{Digest, Sha256};
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::path::PathBuf;
+use std::time::Duration;
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(30);
+/// Longest time without receiving any data.
+const READ_TIMEOUT: Duration = Duration::from_secs(60);
+
+const DEFAULT_MAX_SIZE: u64 = 2 << 30;
+const MAX_SIZE_ENV: &str = "YA_EMSCRIPTEN_MAX_DOWNLOAD_SIZE";
+
+/// Local path of the package at `source`, a path or an http(s) URL.
+/// URLs need `sha256`; for paths it is optional. The package must match it.
+pub fn fetch_package(source: &str, sha256: Option<&str>) -> anyhow::Result<PathBuf> {
+    let sha256 = sha256.map(str::to_lowercase);
+    if source.starts_with("http://") || source.starts_with("https://") {
+        let sha256 = sha256.ok_or_else(|| anyhow!("--sha256 is required for {}", source))?;
+        // Part of the cache file name.
+        if sha256.len() != 64 || !sha256.chars().all(|c| c.is_ascii_hexdigit()) {
+            bail!("--sha256 must be 64 hex digits, got {:?}", sha256);
+        }
+        return timing::phase("fetch", || download(source, &sha256));
+    }
+    if source.starts_with("gftp:") {
+        bail!("gftp URLs are not supported, download {} first", source);
+    }
+
+    let path = PathBuf::from(source);
+    if let Some(expected) = sha256 {
+        if !path.is_file() {
+            bail!("--sha256 can only be checked for package archives");
+        }
+        verify(
+            source,
+            &hex_digest(File::open(&path)?, io::sink())?,
+            &expected,
+        )?;
+    }
+    Ok(path)
+}
+
+fn download(url: &str, sha256: &str) -> anyhow::Result<PathBuf> {
+    let dir = dirs::cache_dir()
+        .ok_or_else(|| anyhow!("no cache directory for downloaded packages"))?
+        .join("ya-runtime-emscripten")
+        .join("images");
+    fs::create_dir_all(&dir)?;
+    let path = dir.join(format!("{}.pkg", sha256));
+    if path.exists() && hex_digest(File::open(&path)?, io::sink())? == sha256 {
+        info!("using cached {}", path.display());
+        return Ok(path);
+    }
+
+    info!("downloading {}", url);
+    let agent = ureq::AgentBuilder::new()
+        .timeout_connect(CONNECT_TIMEOUT)
+        .timeout_read(READ_TIMEOUT)
+        .build();
+    let response = agent
+        .get(url)
+        .call()
+        .with_context(|| format!("failed to download {}", url))?;
+    let max_size = max_size();
+    let too_big = || anyhow!("{} is larger than {} bytes", url, max_size);
+    let length = response
+        .header("Content-Length")
+        .and_then(|length| length.parse::<u64>().ok());
+    if length.is_some_and(|length| length > max_size) {
+        return Err(too_big());
+    }
+    let tmp = dir.join(format!("{}.part", sha256));
+    // One byte over the limit tells a complete body from a cut off one.
+    let body = response.into_reader().take(max_size + 1);
+    let digest = hex_digest(body, File::create(&tmp)?)?;
+    if fs::metadata(&tmp)?.len() > max_size {
+        let _ = fs::remove_file(&tmp);
+        return Err(too_big());
+    }
+    if let Err(e) = verify(url, &digest, sha256) {
+        let _ = fs::remove_file(&tmp);
+        return Err(e);
+    }
+    fs::rename(&tmp, &path)?;
+    Ok(path)
+}
+
+fn max_size() -> u64 {
+    std::env::var(MAX_SIZE_ENV)
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(DEFAULT_MAX_SIZE)
+}
+
+/// Copies `data` to `out`, returning the SHA-256 of the copied bytes.
+pub(crate) fn hex_digest(mut data: impl Read, mut out: impl Write) -> io::Result<String> {
+    let mut hasher = Sha256::new();
+    let mut buf = [0u8; 64 * 1024];
+    loop {
+        let n = data.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        hasher.input(&buf[..n]);
+        out.write_all(&buf[..n])?;
+    }
+    out.flush()?;
+    Ok(format!("{:x}", hasher.result()))
+}
+
+fn verify(source: &str, digest: &str, expected: &str) -> anyhow::Result<()> {
+    if digest != expected {
+        return Err(
+            anyhow!("{}: sha256 is {}, expected {}", source, digest, expected)
+                .context(ErrorClass::BadImage),
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_bad_sha256() {
+        assert!(fetch_package("https://example.com/x.zip", None).is_err());
+        assert!(fetch_package("https://example.com/x.zip", Some("abc")).is_err());
+        let not_hex = "g".repeat(64);
+        assert!(fetch_package("https://example.com/x.zip", Some(&not_hex)).is_err());
+
+        let path = std::env::temp_dir().join(format!("fetch-{}.zip", uuid::Uuid::new_v4()));
+        fs::write(&path, b"package").unwrap();
+        let source = path.to_str().unwrap();
+        let digest = format!("{:x}", Sha256::digest(b"package"));
+        assert_eq!(fetch_package(source, Some(&digest)).unwrap(), path);
+        let e = fetch_package(source, Some(&"0".repeat(64))).unwrap_err();
+        assert_eq!(ErrorClass::of(&e), Some(ErrorClass::BadImage));
+        fs::remove_file(&path).unwrap();
+    }
+}
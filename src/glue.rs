@@ -0,0 +1,88 @@
+//! Support for emscripten glue built with `MODULARIZE` and `EXPORT_ES6`,
+//! which defines a factory instead of running the program as a script.
+
+/// Global holding the prelude's `Module` object while the glue's factory,
+/// usually also named `Module`, is being defined. The prelude's hooks find
+/// the glue's `FS` there, see `prelude`.
+pub(crate) const CONFIG_VAR: &str = "__yaModuleConfig";
+
+/// URL the glue sees as `import.meta.url`.
+const SCRIPT_URL: &str = "'file:///'";
+
+/// Name of the factory exported by modularized glue, `None` for classic
+/// glue that runs on evaluation.
+pub fn factory_name(js: &str) -> Option<&str> {
+    let mut previous = "";
+    for line in js.lines().map(str::trim) {
+        if let Some(name) = line.strip_prefix("export default ") {
+            return Some(name.trim_end_matches(';').trim());
+        }
+        if let Some(name) = line.strip_prefix("module.exports = ") {
+            if previous.contains("typeof exports === 'object' && typeof module === 'object'") {
+                return Some(name.trim_end_matches(';').trim());
+            }
+        }
+        previous = line;
+    }
+    None
+}
+
+/// Glue of a build with `-pthread`, which runs the program on web workers
+/// sharing a `SharedArrayBuffer`. The engine provides neither.
+pub fn uses_pthreads(js: &str) -> bool {
+    js.contains("ENVIRONMENT_IS_PTHREAD")
+}
+
+/// Message for entry points that can't run because they need threads.
+pub const PTHREADS_UNSUPPORTED: &str =
+    "built with -pthread, the engine has no web workers or SharedArrayBuffer";
+
+/// Turns modularized glue into a script that instantiates the factory with
+/// the `Module` object set up by the prelude. Classic glue is returned as is.
+pub fn to_script(js: &str) -> String {
+    let name = match factory_name(js) {
+        Some(name) => name.to_string(),
+        None => return js.to_string(),
+    };
+    let mut script = format!("var {} = Module;\n", CONFIG_VAR);
+    for line in js.lines() {
+        if line.trim_start().starts_with("export default ") {
+            continue;
+        }
+        script.push_str(&line.replace("import.meta.url", SCRIPT_URL));
+        script.push('\n');
+    }
+    script.push_str(&format!("{}({});\n", name, CONFIG_VAR));
+    script
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_to_script() {
+        let classic = "var Module = typeof Module != 'undefined' ? Module : {};\nrun();\n";
+        assert_eq!(factory_name(classic), None);
+        assert!(!uses_pthreads(classic));
+        assert!(uses_pthreads(
+            "var ENVIRONMENT_IS_PTHREAD = Module['ENVIRONMENT_IS_PTHREAD'] || false;\n"
+        ));
+        assert_eq!(to_script(classic), classic);
+
+        let es6 = "var Prog = (() => {\n  var _scriptDir = import.meta.url;\n  return (function(moduleArg = {}) {});\n})();\nexport default Prog;\n";
+        assert_eq!(factory_name(es6), Some("Prog"));
+        assert_eq!(
+            to_script(es6),
+            "var __yaModuleConfig = Module;\nvar Prog = (() => {\n  var _scriptDir = 'file:///';\n  return (function(moduleArg = {}) {});\n})();\nProg(__yaModuleConfig);\n"
+        );
+
+        // The default export name rebinds the prelude's `Module`.
+        let default_name = "var Module = (() => {\n  return (function(moduleArg = {}) { var FS = {}; });\n})();\nexport default Module;\n";
+        assert_eq!(factory_name(default_name), Some("Module"));
+        let script = to_script(default_name);
+        assert!(script.starts_with("var __yaModuleConfig = Module;\nvar Module = (() => {"));
+        assert!(script.ends_with("Module(__yaModuleConfig);\n"));
+        assert!(!script.contains("export default"));
+    }
+}
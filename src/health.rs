@@ -0,0 +1,48 @@
+use crate::events;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::thread;
+use std::time::{Duration, SystemTime};
+use ya_emscripten_meta::HealthProbe;
+
+/// Watches a heartbeat file while the program runs, emitting
+/// `health.unhealthy` / `health.healthy` events on every state change.
+/// Stops when dropped.
+pub struct HealthMonitor {
+    _stop: Sender<()>,
+}
+
+impl HealthMonitor {
+    pub fn start(probe: &HealthProbe, host_file: PathBuf) -> Self {
+        let (stop, stopped) = mpsc::channel::<()>();
+        let timeout = Duration::from_secs(probe.timeout.max(1));
+        let guest_file = probe.file.clone();
+        let started = SystemTime::now();
+
+        thread::spawn(move || {
+            let mut healthy = true;
+            while let Err(RecvTimeoutError::Timeout) = stopped.recv_timeout(timeout / 2) {
+                let last_beat = fs::metadata(&host_file)
+                    .and_then(|meta| meta.modified())
+                    .unwrap_or(started)
+                    .max(started);
+                let stale = SystemTime::now()
+                    .duration_since(last_beat)
+                    .unwrap_or_default()
+                    > timeout;
+                if stale == healthy {
+                    healthy = !stale;
+                    let event = if healthy {
+                        "health.healthy"
+                    } else {
+                        "health.unhealthy"
+                    };
+                    events::emit(event, &[("file", &guest_file)]);
+                }
+            }
+        });
+
+        HealthMonitor { _stop: stop }
+    }
+}
@@ -0,0 +1,55 @@
+use crate::secure_dir;
+use log::warn;
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Guest path of the per-exec home directory.
+pub const HOME_DIR: &str = "/home/golem";
+
+/// File in the home directory the program's exit code is written to.
+pub const EXIT_CODE_FILE: &str = ".exit-code";
+
+/// File in the home directory created once the runtime is initialized,
+/// right before `main`.
+pub const STARTED_FILE: &str = ".started";
+
+/// Host directory backing `HOME_DIR` for a single exec, removed on drop.
+pub struct HomeDir {
+    path: PathBuf,
+}
+
+impl HomeDir {
+    pub fn create(workdir: &Path) -> io::Result<Self> {
+        let id = uuid::Uuid::new_v4().to_hyphenated().to_string();
+        let path = workdir.join(format!(".home-{}", id));
+        secure_dir::create_private_dir(&path)?;
+        Ok(HomeDir { path })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Adds HOME and XDG base directory variables unless already set.
+    pub fn set_env_defaults(&self, env: &mut BTreeMap<String, String>) {
+        let defaults = [
+            ("HOME", HOME_DIR.to_string()),
+            ("XDG_CONFIG_HOME", format!("{}/.config", HOME_DIR)),
+            ("XDG_CACHE_HOME", format!("{}/.cache", HOME_DIR)),
+            ("XDG_DATA_HOME", format!("{}/.local/share", HOME_DIR)),
+        ];
+        for (key, value) in defaults.iter() {
+            env.entry(key.to_string()).or_insert_with(|| value.clone());
+        }
+    }
+}
+
+impl Drop for HomeDir {
+    fn drop(&mut self) {
+        if let Err(e) = fs::remove_dir_all(&self.path) {
+            warn!("failed to remove {}: {}", self.path.display(), e);
+        }
+    }
+}
@@ -0,0 +1,106 @@
+//! Detection of program arguments that look like host paths. Absolute
+//! paths outside every guest mount almost always mean the requestor passed
+//! a path from their own machine, which otherwise only fails inside the
+//! program.
+
+use anyhow::bail;
+use log::warn;
+use std::path::Path;
+
+/// Guest directories emscripten's filesystem creates by itself.
+const BUILTIN_DIRS: &[&str] = &["/tmp", "/dev", "/proc", "/home/web_user"];
+
+/// What to do with arguments that look like host paths.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum HostPathArgs {
+    Allow,
+    Warn,
+    Reject,
+}
+
+impl std::str::FromStr for HostPathArgs {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        match s {
+            "allow" => Ok(HostPathArgs::Allow),
+            "warn" => Ok(HostPathArgs::Warn),
+            "reject" => Ok(HostPathArgs::Reject),
+            _ => bail!(
+                "unknown host path policy: {}, expected allow, warn or reject",
+                s
+            ),
+        }
+    }
+}
+
+/// Applies `policy` to every argument, or value of a `--name=value`
+/// argument, that is an absolute path outside `guest_dirs`.
+pub fn check<'a>(
+    args: &[String],
+    guest_dirs: impl IntoIterator<Item = &'a str>,
+    policy: HostPathArgs,
+) -> anyhow::Result<()> {
+    if policy == HostPathArgs::Allow {
+        return Ok(());
+    }
+    let guest_dirs: Vec<_> = guest_dirs
+        .into_iter()
+        .chain(BUILTIN_DIRS.iter().copied())
+        .map(|dir| Path::new("/").join(dir))
+        .collect();
+    for arg in args {
+        let path = match arg.find("=/") {
+            Some(idx) if arg.starts_with('-') => &arg[idx + 1..],
+            _ if arg.starts_with('/') => arg.as_str(),
+            _ => continue,
+        };
+        if path == "/"
+            || guest_dirs
+                .iter()
+                .any(|dir| Path::new(path).starts_with(dir))
+        {
+            continue;
+        }
+        let message = format!(
+            "argument {:?} looks like a host path outside of mounts",
+            arg
+        );
+        if policy == HostPathArgs::Reject {
+            bail!(message);
+        }
+        warn!("{}", message);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_check() {
+        let args = |args: &[&str]| -> Vec<String> { args.iter().map(|a| a.to_string()).collect() };
+        let dirs = ["/in", "/out", "@"];
+        let reject = HostPathArgs::Reject;
+
+        let ok = args(&[
+            "-v",
+            "/in/data.csv",
+            "--out=/out/x",
+            "/@/lib",
+            "/tmp/t",
+            "rel/path",
+        ]);
+        assert!(check(&ok, dirs.iter().copied(), reject).is_ok());
+
+        for arg in &[
+            "/home/alice/data.csv",
+            "--input=/Users/bob/in.txt",
+            "/input",
+        ] {
+            assert!(check(&args(&[arg]), dirs.iter().copied(), reject).is_err());
+        }
+        assert!(check(&args(&["/etc"]), dirs.iter().copied(), HostPathArgs::Warn).is_ok());
+    }
+}
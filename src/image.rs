@@ -0,0 +1,66 @@
+use crate::deploy::ImageStamp;
+use crate::fetch;
+use crate::package::Package;
+use crate::report::ErrorClass;
+use crate::signature;
+use crate::timing;
+use anyhow::{anyhow, Context};
+use sha2::{Digest, Sha256};
+use std::fs::File;
+use std::io;
+use std::path::Path;
+use ya_emscripten_meta::{EntryPoint, Manifest};
+
+/// Task package together with its manifest.
+#[derive(Debug, Clone)]
+pub struct RuntimeImage {
+    package: Package,
+    manifest: Manifest,
+}
+
+impl RuntimeImage {
+    pub fn open(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let package =
+            timing::phase("package-open", || Package::open(path)).context(ErrorClass::BadImage)?;
+        timing::phase("entry-check", || package.check_entries()).context(ErrorClass::BadImage)?;
+        let manifest = timing::phase("manifest-parse", || package.load_manifest())
+            .context(ErrorClass::BadImage)?;
+        manifest.validate().context(ErrorClass::BadImage)?;
+        Ok(RuntimeImage { package, manifest })
+    }
+
+    pub fn package(&self) -> &Package {
+        &self.package
+    }
+
+    pub fn manifest(&self) -> &Manifest {
+        &self.manifest
+    }
+
+    /// SHA-256 of the archive, or for unpacked packages of the manifest and
+    /// entry point digests covered by the signature.
+    pub fn hash(&self) -> anyhow::Result<String> {
+        match &self.package {
+            Package::Archive(path) => Ok(fetch::hex_digest(File::open(path)?, io::sink())?),
+            Package::Directory(_) => {
+                let digests = signature::signed_digests(&self.package, &self.manifest)?;
+                Ok(format!("{:x}", Sha256::digest(&digests)))
+            }
+        }
+    }
+
+    /// Stamp of the archive, `None` for unpacked packages whose files can
+    /// change without it.
+    pub fn stamp(&self) -> anyhow::Result<Option<ImageStamp>> {
+        match &self.package {
+            Package::Archive(path) => Ok(Some(ImageStamp::of(path)?)),
+            Package::Directory(_) => Ok(None),
+        }
+    }
+
+    pub fn entry_point(&self, id: &str) -> anyhow::Result<&EntryPoint> {
+        self.manifest.find_entry_point(id).ok_or_else(|| {
+            anyhow!("invalid entry point: {}", id).context(ErrorClass::MissingEntryPoint)
+        })
+    }
+}
@@ -0,0 +1,109 @@
+//! `inspect`, an operator's view of a deployment: where every mount lives
+//! on the host, which image it came from and what takes up space. Unlike
+//! `status` it doesn't judge whether the deployment is usable.
+
+use crate::deploy::DeployInfo;
+use crate::report::{RESULTS_DIR, SCHEMA_VERSION};
+use crate::{mounts, volumes, Workdir};
+use serde::Serialize;
+use std::fs;
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "kebab-case")]
+pub struct Inspection {
+    pub schema_version: u32,
+    pub workdir: String,
+    /// Manifest id, for deployments made by this runtime version or later.
+    pub image_id: Option<String>,
+    pub image_hash: Option<String>,
+    pub mounts: Vec<MountInfo>,
+    /// Persistent volumes of the image, shared with its other deployments.
+    pub volumes: Vec<VolumeInfo>,
+    /// Captured output of runs still kept in the workdir.
+    pub outputs: Vec<ExecOutput>,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "kebab-case")]
+pub struct MountInfo {
+    pub guest_path: String,
+    pub mode: &'static str,
+    pub host_dir: String,
+    pub size: u64,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "kebab-case")]
+pub struct VolumeInfo {
+    pub name: String,
+    pub host_dir: String,
+    pub size: u64,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "kebab-case")]
+pub struct ExecOutput {
+    pub exec_id: String,
+    pub size: u64,
+    /// The exec report is still in `results/`.
+    pub has_report: bool,
+}
+
+pub fn inspect(workdir: &Workdir) -> anyhow::Result<Inspection> {
+    let path = fs::canonicalize(workdir.path())?;
+    let info = DeployInfo::load(&path)?;
+
+    let mut mount_info = Vec::new();
+    for (dir, mount_point) in workdir.mounts()? {
+        let host_dir = path.join(dir);
+        mount_info.push(MountInfo {
+            guest_path: mount_point.path().to_string(),
+            mode: mount_point.mode(),
+            size: if host_dir.is_dir() {
+                mounts::tree_size(&host_dir)?
+            } else {
+                0
+            },
+            host_dir: host_dir.display().to_string(),
+        });
+    }
+
+    let mut volume_info = Vec::new();
+    if let Some(hash) = &info.image_hash {
+        for (name, dir) in volumes::list(hash)? {
+            volume_info.push(VolumeInfo {
+                name,
+                size: mounts::tree_size(&dir)?,
+                host_dir: dir.display().to_string(),
+            });
+        }
+    }
+
+    let mut outputs = Vec::new();
+    for entry in fs::read_dir(&path)? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if !entry.file_type()?.is_dir() || uuid::Uuid::parse_str(&name).is_err() {
+            continue;
+        }
+        outputs.push(ExecOutput {
+            size: mounts::tree_size(&entry.path())?,
+            has_report: path
+                .join(RESULTS_DIR)
+                .join(format!("{}.json", name))
+                .exists(),
+            exec_id: name,
+        });
+    }
+    outputs.sort_by(|a, b| a.exec_id.cmp(&b.exec_id));
+
+    Ok(Inspection {
+        schema_version: SCHEMA_VERSION,
+        workdir: path.display().to_string(),
+        image_id: info.image_id,
+        image_hash: info.image_hash,
+        mounts: mount_info,
+        volumes: volume_info,
+        outputs,
+    })
+}
@@ -0,0 +1,103 @@
+//! Emscripten runtime for yagna. The `ya-runtime-emscripten` binary is a
+//! command line front end to `RuntimeImage`, `Workdir` and `Executor`.
+
+mod archive;
+pub mod capture;
+pub mod cli_schema;
+mod crash;
+pub mod deploy;
+pub mod docs;
+pub mod events;
+mod exec_slots;
+mod executor;
+mod extract_cache;
+pub mod fetch;
+pub mod glue;
+mod health;
+mod home;
+pub mod host_paths;
+mod image;
+pub mod inspect;
+pub mod logging;
+mod memory_limit;
+mod mounts;
+pub mod package;
+pub mod preflight;
+mod prelude;
+pub mod priority;
+pub mod query;
+pub mod report;
+pub mod retention;
+pub mod runtime_info;
+pub mod scan;
+pub mod secrets;
+mod secure_dir;
+pub mod signature;
+pub mod spec;
+pub mod stats;
+pub mod status;
+pub mod strict;
+mod tarball;
+mod timing;
+pub mod usage;
+mod volumes;
+pub mod wasm_features;
+mod workdir;
+
+pub use executor::{Executor, RunOptions, DEFAULT_STACK_SIZE};
+pub use image::RuntimeImage;
+pub use workdir::{RunOutput, Workdir};
+
+use anyhow::anyhow;
+use report::ErrorClass;
+use semver::{Version, VersionReq};
+use std::io;
+use std::path::{Component, Path, PathBuf};
+
+/// Version of this runtime, recorded in every exec report.
+pub const RUNTIME_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// Name advertised in offers.
+pub const RUNTIME_NAME: &str = "emscripten";
+
+/// Optional features of this runtime, as advertised in offers.
+pub const CAPABILITIES: &[&str] = &[
+    "data-packages",
+    "secret-env",
+    "output-capture",
+    "output-streaming",
+    "timeout",
+    "mem-limit",
+    "health-probes",
+    "crash-dump",
+    "modularized-glue",
+    "runtime-info",
+    "volumes",
+    "usage-counters",
+    "max-files",
+];
+
+/// Fails unless this runtime's version satisfies `req`.
+pub fn require_runtime_version(req: &VersionReq) -> anyhow::Result<()> {
+    let version = Version::parse(RUNTIME_VERSION)?;
+    if !req.matches(&version) {
+        return Err(
+            anyhow!("runtime version {} does not satisfy {}", version, req)
+                .context(ErrorClass::IncompatibleRuntime),
+        );
+    }
+    Ok(())
+}
+
+/// Turns an absolute or relative guest path into a relative one, rejecting
+/// `..` and other components that could escape the root.
+pub fn normalize_path(path: impl AsRef<Path>) -> io::Result<PathBuf> {
+    path.as_ref()
+        .components()
+        .fold(Ok(PathBuf::from("")), |agg, part| match (agg, part) {
+            (Ok(path), Component::RootDir) => Ok(path),
+            (Ok(path), Component::Prefix(_)) => Ok(path),
+            (Ok(path), Component::Normal(part)) => Ok(path.join(part)),
+            _ => Err(io::Error::from(io::ErrorKind::PermissionDenied)),
+        })
+}
@@ -0,0 +1,57 @@
+//! Diagnostic logging, on stderr or in a file. Runtime events are separate,
+//! see `events`, and are always printed.
+
+use crate::capture::RuntimeStderr;
+use log::LevelFilter;
+use std::fs::OpenOptions;
+use std::io::{self, Write};
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LogFormat {
+    Text,
+    /// One JSON object per line: `{"level":...,"target":...,"message":...}`.
+    Json,
+}
+
+impl std::str::FromStr for LogFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        match s {
+            "text" => Ok(LogFormat::Text),
+            "json" => Ok(LogFormat::Json),
+            _ => anyhow::bail!("unknown log format: {}, expected text or json", s),
+        }
+    }
+}
+
+/// Installs the logger. With `file`, logs are appended there instead of
+/// going to stderr, see `capture::RuntimeStderr`.
+pub fn init(level: LevelFilter, format: LogFormat, file: Option<&Path>) -> io::Result<()> {
+    let mut builder = env_logger::Builder::new();
+    builder.filter_level(level);
+    if format == LogFormat::Json {
+        builder.format(|buf, record| {
+            let line = serde_json::json!({
+                "level": record.level().as_str(),
+                "target": record.target(),
+                "message": record.args().to_string(),
+            });
+            writeln!(buf, "{}", line)
+        });
+    } else {
+        builder.format_timestamp_millis();
+    }
+    match file {
+        Some(path) => {
+            let file = OpenOptions::new().create(true).append(true).open(path)?;
+            builder.target(env_logger::Target::Pipe(Box::new(file)));
+        }
+        None => {
+            builder.target(env_logger::Target::Pipe(Box::new(RuntimeStderr)));
+        }
+    }
+    builder.init();
+    Ok(())
+}
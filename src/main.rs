@@ -1,13 +1,45 @@
-use anyhow::bail;
+use anyhow::{bail, Context};
+use log::{debug, info, LevelFilter};
 use serde::Serialize;
-use sp_wasm_engine::prelude::NodeMode;
-use sp_wasm_engine::sandbox::load::Bytes;
-use sp_wasm_engine::sandbox::Sandbox;
-use std::fs::OpenOptions;
-use std::io;
-use std::path::{Component, Path, PathBuf};
+use sha2::{Digest, Sha256};
+use std::io::Write;
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 use structopt::StructOpt;
-use ya_emscripten_meta::{EntryPoint, Manifest, MountPoint};
+use ya_emscripten_meta::Manifest;
+use ya_runtime_emscripten::deploy::DeployInfo;
+use ya_runtime_emscripten::host_paths::HostPathArgs;
+use ya_runtime_emscripten::logging::{self, LogFormat};
+use ya_runtime_emscripten::package::{DataPackage, Package};
+use ya_runtime_emscripten::priority::Priority;
+use ya_runtime_emscripten::report::{self, ErrorClass, ErrorResponse, ExecReport};
+use ya_runtime_emscripten::retention::Retention;
+use ya_runtime_emscripten::scan::Scanner;
+use ya_runtime_emscripten::signature::SignaturePolicy;
+use ya_runtime_emscripten::spec::ExecSpec;
+use ya_runtime_emscripten::usage::CounterCommand;
+use ya_runtime_emscripten::{
+    capture, cli_schema, deploy, docs, events, fetch, glue, inspect, normalize_path, query,
+    secrets, stats, status, strict, wasm_features, Executor, RunOptions, RunOutput, RuntimeImage,
+    Workdir,
+};
+
+#[derive(StructOpt)]
+#[structopt(rename_all = "kebab-case")]
+struct Args {
+    /// Log level: off, error, warn, info, debug or trace
+    #[structopt(long, global = true, default_value = "info")]
+    log_level: LevelFilter,
+    /// Log format: text or json
+    #[structopt(long, global = true, default_value = "text")]
+    log_format: LogFormat,
+    /// Append logs to this file instead of stderr, e.g. in the workdir
+    #[structopt(long, global = true, parse(from_os_str))]
+    log_file: Option<PathBuf>,
+    #[structopt(subcommand)]
+    command: Opt,
+}
 
 #[derive(StructOpt)]
 #[structopt(rename_all = "kebab-case")]
@@ -17,114 +49,432 @@ enum Opt {
     Open(Open),
     Exec(Exec),
     ResolvePath(Resolve),
+    Docs(Docs),
+    ListEntryPoints(ListEntryPoints),
+    Stats(Stats),
+    Query(Query),
+    OfferTemplate(OfferTemplate),
+    Status(Status),
+    Inspect(Inspect),
 }
 
 #[derive(StructOpt)]
 #[structopt(rename_all = "kebab-case")]
 struct ValidateImage {
-    #[structopt(parse(from_os_str))]
-    image_path: PathBuf,
+    #[structopt(parse(from_os_str), required_unless = "glob")]
+    image_path: Option<PathBuf>,
+    /// Validate every package matching the pattern, e.g. 'dist/*.zip'
+    #[structopt(long, conflicts_with = "image-path")]
+    glob: Option<String>,
+    #[structopt(flatten)]
+    signature: SignaturePolicy,
+    /// Fail instead of falling back, e.g. on '\' separators in manifest paths
+    #[structopt(long)]
+    strict: bool,
 }
 
-fn load_manifest(image_path: &Path) -> anyhow::Result<Manifest> {
-    let mut a = zip::ZipArchive::new(OpenOptions::new().read(true).open(image_path)?)?;
-
-    let entry = a.by_name("gu-package.json")?;
-
-    Ok(serde_json::from_reader(entry)?)
-}
-
-fn normalize_path(path: impl AsRef<Path>) -> io::Result<PathBuf> {
-    path.as_ref()
-        .components()
-        .fold(Ok(PathBuf::from("")), |agg, part| match (agg, part) {
-            (Ok(path), Component::RootDir) => Ok(path),
-            (Ok(path), Component::Prefix(_)) => Ok(path),
-            (Ok(path), Component::Normal(part)) => Ok(path.join(part)),
-            _ => Err(io::Error::from(io::ErrorKind::PermissionDenied)),
-        })
+/// Engine settings shared by `open` and `exec`.
+#[derive(StructOpt, Debug)]
+#[structopt(rename_all = "kebab-case")]
+struct EngineOpts {
+    /// Native stack size of the engine thread in bytes
+    #[structopt(long)]
+    stack_size: Option<usize>,
+    /// Wall-clock limit of the run in seconds
+    #[structopt(long)]
+    timeout: Option<u64>,
+    /// Limit in seconds on the time until the program's main is called
+    #[structopt(long)]
+    startup_timeout: Option<u64>,
+    /// Scheduling class of the engine thread: realtime, normal or background
+    #[structopt(long, default_value = "normal")]
+    priority: Priority,
+    /// Maximum linear memory of the program in bytes
+    #[structopt(long)]
+    mem_limit: Option<u64>,
+    /// Stop the run once writable mounts hold more than this many bytes
+    #[structopt(long)]
+    output_quota: Option<u64>,
+    /// Stop the run once writable mounts hold more than this many files and directories
+    #[structopt(long)]
+    max_files: Option<u64>,
+    /// Inherited file descriptor to read secret environment variables from (JSON object)
+    #[structopt(long)]
+    secrets_fd: Option<i32>,
+    /// Don't fsync writable mounts after the run
+    #[structopt(long)]
+    no_fsync: bool,
+    /// Mount an additional package read-only: <guest-path>=<package-path>
+    #[structopt(long = "data-package", number_of_values = 1)]
+    data_packages: Vec<DataPackage>,
+    /// Mount the captured output of an earlier run read-only: <exec-id>=<guest-path>
+    #[structopt(long = "run-output", number_of_values = 1)]
+    run_outputs: Vec<RunOutput>,
+    /// Refuse to run unless the runtime version matches this semver requirement
+    #[structopt(long)]
+    require_runtime_version: Option<semver::VersionReq>,
+    /// Scanner command run on inputs and writable mounts, '{}' is the path
+    #[structopt(long)]
+    scan_command: Option<Scanner>,
+    /// Command adding usage counters after the run, '{}' is the exec directory
+    #[structopt(long, number_of_values = 1)]
+    usage_counter_command: Vec<CounterCommand>,
+    /// Keep the report and output tails under crash/ when the engine fails
+    #[structopt(long)]
+    crash_dump: bool,
+    /// Stream the program's stdout and stderr as length-prefixed frames on stdout
+    #[structopt(long)]
+    stream_output: bool,
+    /// Wait while this many other runs use the workdir
+    #[structopt(long)]
+    max_concurrent: Option<NonZeroUsize>,
+    /// Feed the program's stdin from this file, '-' for the runtime's stdin, 'none'
+    /// for no stdin [default for exec: '-' unless stdin is a terminal]
+    #[structopt(long, parse(from_os_str))]
+    stdin: Option<PathBuf>,
+    /// Arguments that look like host paths outside of mounts: allow, warn or reject
+    #[structopt(long, default_value = "warn")]
+    host_path_args: HostPathArgs,
+    /// Fail instead of falling back, e.g. on '\' separators in manifest paths
+    #[structopt(long)]
+    strict: bool,
+    /// Also write events as JSON lines to this unix socket
+    #[structopt(long, parse(from_os_str))]
+    events_socket: Option<PathBuf>,
+    /// Print the exec report of a successful run on stdout
+    #[structopt(long, conflicts_with = "stream-output")]
+    json: bool,
+    #[structopt(flatten)]
+    retention: Retention,
 }
 
-fn run_ep(
-    image_path: &Path,
-    workdir: &Path,
-    ep: &EntryPoint,
-    m: &Manifest,
-    args: Vec<String>,
-) -> anyhow::Result<()> {
-    let wasm_path = normalize_path(&ep.wasm_path)?;
-    let js_path = wasm_path.with_extension("js");
-
-    let (js_bytes, wasm_bytes) = {
-        let mut a = zip::ZipArchive::new(OpenOptions::new().read(true).open(image_path)?)?;
-
-        eprintln!("js={}, wasm={}", js_path.display(), wasm_path.display());
-
-        let wasm = a.by_name(wasm_path.to_string_lossy().as_ref())?;
-        let wasm_bytes = Bytes::from_reader(wasm)?;
-        let js_bytes = Bytes::from_reader(a.by_name(js_path.to_string_lossy().as_ref())?)?;
-
-        (js_bytes, wasm_bytes)
-    };
-
-    let mut sb = Sandbox::new().map_err(anyhow::Error::msg)?;
+impl EngineOpts {
+    /// Applies process-wide settings and loads the spec, before the image
+    /// is opened.
+    fn setup(&self, spec: &Path) -> anyhow::Result<ExecSpec> {
+        if self.strict {
+            strict::enable();
+        }
+        if let Some(path) = &self.events_socket {
+            events::connect_socket(path)?;
+        }
+        ExecSpec::load(spec)
+    }
 
-    if let Some(work_dir) = &m.work_dir {
-        sb = sb.work_dir(work_dir).map_err(anyhow::Error::msg)?;
+    fn print_report(&self, report: &ExecReport) -> anyhow::Result<()> {
+        if self.json {
+            println!("{}", serde_json::to_string_pretty(report)?);
+        }
+        Ok(())
     }
-    let mounts: Vec<(String, MountPoint)> =
-        serde_json::from_slice(std::fs::read(workdir.join("mounts.json"))?.as_slice())?;
-    sb = sb.set_exec_args(args).map_err(anyhow::Error::msg)?;
-    sb.init().map_err(anyhow::Error::msg)?;
-    sb.mount(&image_path, "@", NodeMode::Ro)?;
 
-    for (path, mount_point) in mounts {
-        sb.mount(workdir.join(path), mount_point.path(), NodeMode::Rw)?;
+    fn run_options(&self, args: Vec<String>, spec: ExecSpec) -> anyhow::Result<RunOptions> {
+        if let Some(req) = &self.require_runtime_version {
+            ya_runtime_emscripten::require_runtime_version(req)?;
+        }
+        let args = if args.is_empty() { spec.args } else { args };
+        let mut env = spec.env;
+        if let Some(fd) = self.secrets_fd {
+            env.extend(secrets::read_secrets(fd)?);
+        }
+        Ok(RunOptions {
+            args,
+            env,
+            stack_size: self.stack_size.or(spec.limits.stack_size),
+            timeout: self
+                .timeout
+                .or(spec.limits.timeout_sec)
+                .map(Duration::from_secs),
+            startup_timeout: self
+                .startup_timeout
+                .or(spec.limits.startup_timeout_sec)
+                .map(Duration::from_secs),
+            priority: self.priority,
+            mem_limit: self.mem_limit.or(spec.limits.mem_limit),
+            output_quota: self.output_quota.or(spec.limits.output_quota),
+            max_files: self.max_files.or(spec.limits.max_files),
+            fsync: !self.no_fsync,
+            data_packages: self.data_packages.clone(),
+            run_outputs: self.run_outputs.clone(),
+            extra_mounts: spec.mounts,
+            args_sha256: None,
+            scanner: self.scan_command.clone(),
+            usage_counters: self.usage_counter_command.clone(),
+            crash_dump: self.crash_dump,
+            stream_output: self.stream_output,
+            stdin: self.stdin.clone().filter(|path| path != Path::new("none")),
+            host_path_args: self.host_path_args,
+            max_concurrent: self.max_concurrent.map(NonZeroUsize::get),
+        })
     }
+}
 
-    let _ = sb.run(js_bytes, wasm_bytes).map_err(anyhow::Error::msg)?;
+/// Single problem found by `validate-image`.
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "kebab-case")]
+struct Diagnostic {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    entry_point: Option<String>,
+    /// Set for manifest problems, see `ValidationError::code`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    code: Option<&'static str>,
+    message: String,
+}
+
+#[derive(Serialize, Debug)]
+struct ValidationReport {
+    schema_version: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    path: Option<String>,
+    valid: bool,
+    errors: Vec<Diagnostic>,
+    warnings: Vec<Diagnostic>,
+}
 
-    Ok(())
+#[derive(Serialize, Debug)]
+struct BatchValidationReport {
+    schema_version: u32,
+    valid: bool,
+    packages: Vec<ValidationReport>,
 }
 
 impl ValidateImage {
     fn execute(self) -> anyhow::Result<()> {
-        // Getting image
-        let mut a = zip::ZipArchive::new(OpenOptions::new().read(true).open(self.image_path)?)?;
+        if self.strict {
+            strict::enable();
+        }
+        if let Some(pattern) = &self.glob {
+            let mut packages = Vec::new();
+            for path in glob::glob(pattern)? {
+                let path = path?;
+                let mut report = validate_package(&path, &self.signature);
+                report.path = Some(path.display().to_string());
+                packages.push(report);
+            }
+            let valid = packages.iter().all(|report| report.valid);
+            let invalid = packages.iter().filter(|report| !report.valid).count();
+            println!(
+                "{}",
+                serde_json::to_string_pretty(&BatchValidationReport {
+                    schema_version: report::SCHEMA_VERSION,
+                    valid,
+                    packages
+                })?
+            );
+            if !valid {
+                return Err(
+                    anyhow::anyhow!("{} invalid packages matching {}", invalid, pattern)
+                        .context(ErrorClass::BadImage),
+                );
+            }
+            return Ok(());
+        }
 
-        let entry = a.by_name("manifest.json")?;
-        let m: Manifest = serde_json::from_reader(entry)?;
+        let image_path = self.image_path.unwrap();
+        let report = validate_package(&image_path, &self.signature);
+        println!("{}", serde_json::to_string_pretty(&report)?);
+        if !report.valid {
+            return Err(anyhow::anyhow!("invalid image: {}", image_path.display())
+                .context(ErrorClass::BadImage));
+        }
+        Ok(())
+    }
+}
 
-        eprintln!("m={:?}", m);
+fn validate_package(image_path: &Path, signature: &SignaturePolicy) -> ValidationReport {
+    events::emit("validate.start", &[("image", &image_path.display())]);
+    let mut warnings = Vec::new();
+    let errors = match Package::open(image_path).and_then(|package| {
+        package.check_entries()?;
+        let mut m = package.load_raw_manifest()?;
+        let mut fallbacks = Vec::new();
+        for fix in m.sanitize() {
+            fallbacks.push(Diagnostic {
+                entry_point: None,
+                code: Some("backslash-path"),
+                message: format!(
+                    "{} uses '\\' separators: {:?}, read as {:?}",
+                    fix.field, fix.original, fix.fixed
+                ),
+            });
+        }
+        let mut errors = check_image(&package, &m);
+        if strict::is_enabled() {
+            errors.extend(fallbacks);
+        } else {
+            warnings.extend(fallbacks);
+        }
+        if let Err(e) = signature.check(&package, &m) {
+            errors.push(Diagnostic {
+                entry_point: None,
+                code: Some("signature"),
+                message: e.to_string(),
+            });
+        }
+        Ok(errors)
+    }) {
+        Ok(errors) => errors,
+        Err(e) => vec![Diagnostic {
+            entry_point: None,
+            code: None,
+            message: e.to_string(),
+        }],
+    };
+    if errors.is_empty() {
+        events::emit("validate.done", &[("image", &image_path.display())]);
+    }
+    ValidationReport {
+        schema_version: report::SCHEMA_VERSION,
+        path: None,
+        valid: errors.is_empty(),
+        errors,
+        warnings,
+    }
+}
 
-        Ok(())
+/// Collects all problems of a package with a parsed manifest.
+fn check_image(package: &Package, m: &Manifest) -> Vec<Diagnostic> {
+    let mut errors: Vec<Diagnostic> = m
+        .problems()
+        .into_iter()
+        .map(|e| Diagnostic {
+            entry_point: None,
+            code: Some(e.code()),
+            message: e.to_string(),
+        })
+        .collect();
+
+    for ep in m.main.iter().chain(&m.entry_points) {
+        let mut error = |message: String| {
+            errors.push(Diagnostic {
+                entry_point: Some(ep.id.clone()),
+                code: None,
+                message,
+            })
+        };
+
+        let wasm_path = match normalize_path(&ep.wasm_path) {
+            Ok(path) => path,
+            Err(_) => {
+                error(format!("invalid wasm path: {}", ep.wasm_path));
+                continue;
+            }
+        };
+        let js_path = wasm_path.with_extension("js");
+        match package.has_entry(&js_path) {
+            Ok(true) => match package.read_entry(&js_path) {
+                Ok(js) if glue::uses_pthreads(&String::from_utf8_lossy(&js)) => {
+                    error(glue::PTHREADS_UNSUPPORTED.to_string())
+                }
+                Ok(_) => (),
+                Err(e) => error(e.to_string()),
+            },
+            Ok(false) => error(format!("missing {}", js_path.display())),
+            Err(e) => error(e.to_string()),
+        }
+        match package.has_entry(&wasm_path) {
+            Ok(true) => (),
+            Ok(false) => {
+                error(format!("missing {}", wasm_path.display()));
+                continue;
+            }
+            Err(e) => {
+                error(e.to_string());
+                continue;
+            }
+        }
+        match package
+            .read_entry(&wasm_path)
+            .and_then(|wasm| wasm_features::unsupported_features(&wasm))
+        {
+            Ok(unsupported) if unsupported.is_empty() => (),
+            Ok(unsupported) => {
+                let names: Vec<String> = unsupported.iter().map(ToString::to_string).collect();
+                error(format!(
+                    "needs wasm features not supported by the engine: {}",
+                    names.join(", ")
+                ));
+            }
+            Err(e) => error(format!("{}: {}", wasm_path.display(), e)),
+        }
     }
+    errors
 }
 
 #[derive(StructOpt, Debug)]
 #[structopt(rename_all = "kebab-case")]
 struct Create {
-    #[structopt(short, long, parse(from_os_str))]
-    task_package: PathBuf,
+    /// Package path or http(s) URL
+    #[structopt(short, long)]
+    task_package: String,
+    /// Expected SHA-256 of the package, required for URLs
+    #[structopt(long)]
+    sha256: Option<String>,
     #[structopt(short, long, parse(from_os_str))]
     workdir: PathBuf,
+    #[structopt(flatten)]
+    retention: Retention,
+    #[structopt(flatten)]
+    signature: SignaturePolicy,
+    /// Fail instead of falling back, e.g. on '\' separators in manifest paths
+    #[structopt(long)]
+    strict: bool,
+    /// Also write events as JSON lines to this unix socket
+    #[structopt(long, parse(from_os_str))]
+    events_socket: Option<PathBuf>,
+    /// Print the deployment on stdout
+    #[structopt(long)]
+    json: bool,
+}
+
+/// Result of `deploy --json`.
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "kebab-case")]
+struct DeployResult {
+    schema_version: u32,
+    image_hash: Option<String>,
+    mounts: Vec<DeployedMount>,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "kebab-case")]
+struct DeployedMount {
+    guest_path: String,
+    host_path: PathBuf,
 }
 
 impl Create {
     fn execute(self) -> anyhow::Result<()> {
-        let m = load_manifest(&self.task_package)?;
-        let mut args = Vec::new();
-        for mount_point in m.mount_points {
-            let id = uuid::Uuid::new_v4();
-            let id_str = id.to_hyphenated().to_string();
-            let full_path = self.workdir.join(&id_str);
-            std::fs::create_dir(full_path)?;
-            args.push((id_str, mount_point));
-        }
-        std::fs::write(
-            self.workdir.join("mounts.json"),
-            serde_json::to_vec_pretty(&args)?,
-        )?;
+        if self.strict {
+            strict::enable();
+        }
+        if let Some(path) = &self.events_socket {
+            events::connect_socket(path)?;
+        }
+        let path = fetch::fetch_package(&self.task_package, self.sha256.as_deref())?;
+        info!("task package: {}", path.display());
+        let image = RuntimeImage::open(&path)?;
+        self.signature
+            .check(image.package(), image.manifest())
+            .context(ErrorClass::BadImage)?;
+        let workdir = Workdir::new(self.workdir);
+        self.retention.prune(workdir.path())?;
+        workdir.deploy(&image)?;
+        if self.json {
+            let result = DeployResult {
+                schema_version: report::SCHEMA_VERSION,
+                image_hash: DeployInfo::load(workdir.path())?.image_hash,
+                mounts: workdir
+                    .mounts()?
+                    .into_iter()
+                    .map(|(dir, mount_point)| DeployedMount {
+                        guest_path: mount_point.path().to_string(),
+                        host_path: workdir.path().join(dir),
+                    })
+                    .collect(),
+            };
+            println!("{}", serde_json::to_string_pretty(&result)?);
+        }
         Ok(())
     }
 }
@@ -144,32 +494,17 @@ struct Resolve {
 
 impl Resolve {
     fn execute(self) -> anyhow::Result<()> {
-        eprintln!("WASM: resolve path {:?}", self);
-
-        let mounts: Vec<(String, MountPoint)> =
-            serde_json::from_slice(std::fs::read(self.workdir.join("mounts.json"))?.as_slice())?;
-
-        let _base = PathBuf::from("");
-        let output = PathBuf::from(self.destination);
-
-        let work_dir: PathBuf = normalize_path(&output)?;
-
-        let mut result = ResolveResult::UnresolvedPath;
-        for (dest, mount_point) in mounts {
-            let mount_path = normalize_path(mount_point.path())?;
-            if work_dir.starts_with(&mount_path) {
-                result = ResolveResult::ResolvedPath(
-                    self.workdir
-                        .join(dest)
-                        .join(work_dir.strip_prefix(&mount_path)?)
-                        .display()
-                        .to_string(),
-                );
-                break;
-            } else {
-                eprintln!("{} -- {}", work_dir.display(), mount_point.path())
-            }
-        }
+        debug!("resolve path {:?}", self);
+
+        let spec = ExecSpec::load(&self.spec)?;
+        let host_path = match spec.resolve(&self.destination)? {
+            Some(path) => Some(path),
+            None => Workdir::new(&self.workdir).resolve(&self.destination)?,
+        };
+        let result = match host_path {
+            Some(path) => ResolveResult::ResolvedPath(path.display().to_string()),
+            None => ResolveResult::UnresolvedPath,
+        };
 
         println!("{}", serde_json::to_string_pretty(&result)?);
         Ok(())
@@ -192,13 +527,24 @@ struct Open {
     workdir: PathBuf,
     #[structopt(long, parse(from_os_str))]
     spec: PathBuf,
+    #[structopt(flatten)]
+    engine: EngineOpts,
 }
 
 impl Open {
     fn execute(self) -> anyhow::Result<()> {
-        let m = load_manifest(&self.image)?;
-        if let Some(main_ep) = &m.main {
-            run_ep(&self.image, &self.workdir, main_ep, &m, Vec::new())?;
+        let spec = self.engine.setup(&self.spec)?;
+        let image = RuntimeImage::open(&self.image)?;
+        let ep = match &spec.entry_point {
+            Some(id) => Some(image.entry_point(id)?),
+            None => image.manifest().main.as_ref(),
+        };
+        if let Some(ep) = ep {
+            let workdir = Workdir::new(self.workdir);
+            self.engine.retention.prune(workdir.path())?;
+            let opts = self.engine.run_options(Vec::new(), spec)?;
+            let report = Executor::new(&image, &workdir).run(ep, opts)?;
+            self.engine.print_report(&report)?;
         }
         Ok(())
     }
@@ -213,6 +559,11 @@ struct Exec {
     workdir: PathBuf,
     #[structopt(long, parse(from_os_str))]
     spec: PathBuf,
+    #[structopt(flatten)]
+    engine: EngineOpts,
+    /// Read program arguments from a JSON array of strings instead
+    #[structopt(long, parse(from_os_str))]
+    args_file: Option<PathBuf>,
 
     prog: String,
     args: Vec<String>,
@@ -220,22 +571,313 @@ struct Exec {
 
 impl Exec {
     fn execute(self) -> anyhow::Result<()> {
-        let m = load_manifest(&self.image)?;
-        if let Some(ep) = m.entry_points.iter().find(|&ep| ep.id == self.prog) {
-            run_ep(&self.image, &self.workdir, ep, &m, self.args)?;
-        } else {
-            bail!("invalid entry point: {}", self.prog);
+        let spec = self.engine.setup(&self.spec)?;
+        let image = RuntimeImage::open(&self.image)?;
+        let ep = image.entry_point(&self.prog)?;
+        let (args, args_sha256) = match &self.args_file {
+            Some(path) => {
+                if !self.args.is_empty() {
+                    bail!("--args-file can't be combined with command line arguments");
+                }
+                let data = std::fs::read(path)?;
+                let args: Vec<String> = serde_json::from_slice(&data)?;
+                (args, Some(format!("{:x}", Sha256::digest(&data))))
+            }
+            None => (self.args, None),
+        };
+        let workdir = Workdir::new(self.workdir);
+        self.engine.retention.prune(workdir.path())?;
+        let mut opts = self.engine.run_options(args, spec)?;
+        opts.args_sha256 = args_sha256;
+        // Like a CLI tool, the program reads piped input.
+        if self.engine.stdin.is_none() && !stdin_is_terminal() {
+            opts.stdin = Some(PathBuf::from("-"));
+        }
+        let report = Executor::new(&image, &workdir).run(ep, opts)?;
+        self.engine.print_report(&report)
+    }
+}
+
+#[cfg(unix)]
+fn stdin_is_terminal() -> bool {
+    // Safety: isatty only inspects the descriptor.
+    unsafe { libc::isatty(libc::STDIN_FILENO) == 1 }
+}
+
+#[cfg(not(unix))]
+fn stdin_is_terminal() -> bool {
+    true
+}
+
+#[derive(StructOpt, Debug)]
+#[structopt(rename_all = "kebab-case")]
+struct Docs {
+    #[structopt(parse(from_os_str))]
+    image: PathBuf,
+    /// Output format: md or json
+    #[structopt(long, default_value = "md")]
+    out: DocsFormat,
+}
+
+#[derive(Debug)]
+enum DocsFormat {
+    Markdown,
+    Json,
+}
+
+impl std::str::FromStr for DocsFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        match s {
+            "md" => Ok(DocsFormat::Markdown),
+            "json" => Ok(DocsFormat::Json),
+            _ => bail!("unknown docs format: {}", s),
+        }
+    }
+}
+
+impl Docs {
+    fn execute(self) -> anyhow::Result<()> {
+        let image = RuntimeImage::open(&self.image)?;
+        let m = image.manifest();
+        match self.out {
+            DocsFormat::Markdown => print!("{}", docs::markdown(m)),
+            DocsFormat::Json => println!("{}", serde_json::to_string_pretty(m)?),
+        }
+        Ok(())
+    }
+}
+
+/// Prints the entry points of an image as JSON.
+#[derive(StructOpt, Debug)]
+#[structopt(rename_all = "kebab-case")]
+struct ListEntryPoints {
+    #[structopt(parse(from_os_str))]
+    image: PathBuf,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "kebab-case")]
+struct EntryPointListing<'a> {
+    id: &'a str,
+    /// Run by `open` rather than `exec`.
+    main: bool,
+    wasm_path: &'a str,
+    args_prefix: &'a [String],
+    #[serde(skip_serializing_if = "Option::is_none")]
+    timeout: Option<u64>,
+    /// Names of the entry point's default environment variables.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    env: Vec<&'a str>,
+}
+
+impl ListEntryPoints {
+    fn execute(self) -> anyhow::Result<()> {
+        let image = RuntimeImage::open(&self.image)?;
+        let m = image.manifest();
+        let main = m.main.iter().map(|ep| (true, ep));
+        let listing: Vec<EntryPointListing> = main
+            .chain(m.entry_points.iter().map(|ep| (false, ep)))
+            .map(|(main, ep)| EntryPointListing {
+                id: &ep.id,
+                main,
+                wasm_path: &ep.wasm_path,
+                args_prefix: &ep.args_prefix,
+                timeout: ep.timeout,
+                env: ep.env.keys().map(String::as_str).collect(),
+            })
+            .collect();
+        println!("{}", serde_json::to_string_pretty(&listing)?);
+        Ok(())
+    }
+}
+
+#[derive(StructOpt, Debug)]
+#[structopt(rename_all = "kebab-case")]
+struct Stats {
+    #[structopt(long, parse(from_os_str))]
+    image: PathBuf,
+    /// Only show this entry point
+    #[structopt(long)]
+    entry_point: Option<String>,
+}
+
+impl Stats {
+    fn execute(self) -> anyhow::Result<()> {
+        let image = RuntimeImage::open(&self.image)?;
+        let mut summary = stats::summarize(&image.manifest().id)?;
+        if let Some(id) = &self.entry_point {
+            summary.retain(|ep, _| ep == id);
+        }
+        println!("{}", serde_json::to_string_pretty(&summary)?);
+        Ok(())
+    }
+}
+
+/// Evaluates a path expression over `{deploy, mounts, result}` of a workdir,
+/// where `result` is the latest exec report.
+#[derive(StructOpt, Debug)]
+#[structopt(rename_all = "kebab-case")]
+struct Query {
+    /// jq-like path, e.g. .result.status.return_code
+    expr: String,
+    #[structopt(long, parse(from_os_str))]
+    workdir: PathBuf,
+    /// Print strings without JSON quoting
+    #[structopt(short, long)]
+    raw_output: bool,
+}
+
+impl Query {
+    fn execute(self) -> anyhow::Result<()> {
+        let read = |path: PathBuf| -> anyhow::Result<serde_json::Value> {
+            if path.exists() {
+                Ok(serde_json::from_slice(&std::fs::read(path)?)?)
+            } else {
+                Ok(serde_json::Value::Null)
+            }
+        };
+        let result = match report::latest(&self.workdir)? {
+            Some(path) => read(path)?,
+            None => serde_json::Value::Null,
+        };
+        let doc = serde_json::json!({
+            "deploy": read(self.workdir.join(deploy::DEPLOY_FILE))?,
+            "mounts": read(self.workdir.join(deploy::MOUNTS_FILE))?,
+            "result": result,
+        });
+
+        match query::eval(&self.expr, &doc)? {
+            serde_json::Value::String(s) if self.raw_output => println!("{}", s),
+            value => println!("{}", serde_json::to_string_pretty(value)?),
         }
         Ok(())
     }
 }
 
+/// Prints a JSON summary of a workdir: deployment validity, mounts, the
+/// last run and disk usage.
+#[derive(StructOpt, Debug)]
+#[structopt(rename_all = "kebab-case")]
+struct Status {
+    #[structopt(long, parse(from_os_str))]
+    workdir: PathBuf,
+    /// Also check the deployment against this image
+    #[structopt(long, parse(from_os_str))]
+    image: Option<PathBuf>,
+}
+
+impl Status {
+    fn execute(self) -> anyhow::Result<()> {
+        let image = match &self.image {
+            Some(path) => Some(RuntimeImage::open(path)?),
+            None => None,
+        };
+        let status = status::collect(&Workdir::new(&self.workdir), image.as_ref())?;
+        println!("{}", serde_json::to_string_pretty(&status)?);
+        Ok(())
+    }
+}
+
+/// Prints where a deployment's mounts live on the host, the image it was
+/// deployed from, volume disk usage and kept run outputs.
+#[derive(StructOpt, Debug)]
+#[structopt(rename_all = "kebab-case")]
+struct Inspect {
+    #[structopt(long, parse(from_os_str))]
+    workdir: PathBuf,
+}
+
+impl Inspect {
+    fn execute(self) -> anyhow::Result<()> {
+        let inspection = inspect::inspect(&Workdir::new(&self.workdir))?;
+        println!("{}", serde_json::to_string_pretty(&inspection)?);
+        Ok(())
+    }
+}
+
+/// Prints the offer template ya-provider advertises this runtime with.
+#[derive(StructOpt, Debug)]
+#[structopt(rename_all = "kebab-case")]
+struct OfferTemplate {}
+
+impl OfferTemplate {
+    fn execute(self) -> anyhow::Result<()> {
+        let features: Vec<String> = wasm_features::ENGINE_FEATURES
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        let template = serde_json::json!({
+            "properties": {
+                "golem.runtime.name": ya_runtime_emscripten::RUNTIME_NAME,
+                "golem.runtime.version": ya_runtime_emscripten::RUNTIME_VERSION,
+                "golem.runtime.capabilities": ya_runtime_emscripten::CAPABILITIES,
+                "golem.runtime.wasm.features": features,
+            },
+            "constraints": "",
+        });
+        println!("{}", serde_json::to_string_pretty(&template)?);
+        Ok(())
+    }
+}
+
 fn main() {
-    match Opt::from_args() {
-        Opt::ValidateImage(command) => command.execute().unwrap(),
-        Opt::Deploy(command) => command.execute().unwrap(),
-        Opt::ResolvePath(command) => command.execute().unwrap(),
-        Opt::Open(command) => command.execute().unwrap(),
-        Opt::Exec(command) => command.execute().unwrap(),
+    // Handled before parsing, which would demand a subcommand.
+    if std::env::args().nth(1).as_deref() == Some("--help-json") {
+        let schema = cli_schema::describe(&Args::clap());
+        match serde_json::to_string_pretty(&schema) {
+            Ok(json) => println!("{}", json),
+            Err(e) => eprintln!("failed to serialize the command line schema: {}", e),
+        }
+        return;
+    }
+    let args = Args::from_args();
+    if let Err(e) = logging::init(args.log_level, args.log_format, args.log_file.as_deref()) {
+        eprintln!("failed to open the log file: {}", e);
+        std::process::exit(report::exit_code(&anyhow::Error::from(e)));
+    }
+    let streaming = match &args.command {
+        Opt::Open(command) => command.engine.stream_output,
+        Opt::Exec(command) => command.engine.stream_output,
+        _ => false,
+    };
+    let result = match args.command {
+        Opt::ValidateImage(command) => command.execute(),
+        Opt::Deploy(command) => command.execute(),
+        Opt::ResolvePath(command) => command.execute(),
+        Opt::Open(command) => command.execute(),
+        Opt::Exec(command) => command.execute(),
+        Opt::Docs(command) => command.execute(),
+        Opt::ListEntryPoints(command) => command.execute(),
+        Opt::Stats(command) => command.execute(),
+        Opt::Query(command) => command.execute(),
+        Opt::OfferTemplate(command) => command.execute(),
+        Opt::Status(command) => command.execute(),
+        Opt::Inspect(command) => command.execute(),
+    };
+    if let Err(e) = &result {
+        events::emit("command.failed", &[("error", e)]);
+        // A timed out program may still hold fds 1 and 2, see `capture`.
+        let mut stdout = capture::RuntimeStdout;
+        let mut stderr = capture::RuntimeStderr;
+        let _ = writeln!(stderr, "error: {:?}", e);
+        match serde_json::to_string(&ErrorResponse::from_error(e)) {
+            // Stdout carries frames, see `capture`.
+            Ok(json) if streaming => {
+                if let Err(e) =
+                    capture::write_frame(&mut stdout, capture::ERROR_ID, json.as_bytes())
+                {
+                    let _ = writeln!(stderr, "failed to write the error frame: {}", e);
+                }
+            }
+            Ok(json) => {
+                let _ = writeln!(stdout, "{}", json);
+            }
+            Err(json_error) => {
+                let _ = writeln!(stderr, "failed to serialize error: {}", json_error);
+            }
+        }
+        std::process::exit(report::exit_code(e));
     }
 }
@@ -0,0 +1,141 @@
+use crate::report::ErrorClass;
+use crate::strict;
+use crate::wasm_features::{Reader, LIMITS_HAS_MAX, SECTION_IMPORT, SECTION_MEMORY, WASM_MAGIC};
+use anyhow::{anyhow, bail};
+
+pub const WASM_PAGE_SIZE: u64 = 64 * 1024;
+
+const LIMITS_MEMORY64: u8 = 0x04;
+
+/// Caps the linear memory of a module at `limit` bytes by lowering the
+/// maximum of its memories, so growing past the budget fails inside the
+/// program. Fails if the module needs more than `limit` from the start.
+///
+/// Imported memories are created by the glue and can only be checked, not
+/// capped.
+pub fn limit_memory(wasm: &[u8], limit: u64) -> anyhow::Result<Vec<u8>> {
+    if wasm.len() < 8 || &wasm[..4] != WASM_MAGIC {
+        bail!("not a wasm module");
+    }
+    let max_pages = limit / WASM_PAGE_SIZE;
+
+    let mut out = wasm[..8].to_vec();
+    let mut r = Reader::new(&wasm[8..]);
+    while !r.is_empty() {
+        let id = r.u8()?;
+        let size = r.leb()? as usize;
+        let contents = r.take(size)?;
+        let mut section = Reader::new(contents);
+        match id {
+            SECTION_MEMORY => {
+                let mut patched = Vec::new();
+                let count = section.leb()?;
+                write_leb(&mut patched, count);
+                for _ in 0..count {
+                    let (flags, initial, max) = limits(&mut section)?;
+                    check_initial(initial, max_pages, limit)?;
+                    let max = max.map_or(max_pages, |max| max.min(max_pages));
+                    patched.push(flags | LIMITS_HAS_MAX);
+                    write_leb(&mut patched, initial);
+                    write_leb(&mut patched, max);
+                }
+                out.push(id);
+                write_leb(&mut out, patched.len() as u64);
+                out.extend(patched);
+                continue;
+            }
+            SECTION_IMPORT => {
+                for _ in 0..section.leb()? {
+                    section.name()?;
+                    section.name()?;
+                    match section.u8()? {
+                        0 => {
+                            section.leb()?;
+                        }
+                        1 => {
+                            section.u8()?;
+                            limits(&mut section)?;
+                        }
+                        2 => {
+                            let (_, initial, _) = limits(&mut section)?;
+                            check_initial(initial, max_pages, limit)?;
+                            strict::fallback(
+                                "uncapped-memory",
+                                format!("imported memory can't be capped at {} bytes", limit),
+                            )?;
+                        }
+                        3 => {
+                            section.u8()?;
+                            section.u8()?;
+                        }
+                        4 => {
+                            section.u8()?;
+                            section.leb()?;
+                        }
+                        // A memory import behind it would go unchecked.
+                        kind => bail!("unknown import kind {}", kind),
+                    }
+                }
+            }
+            _ => (),
+        }
+        out.push(id);
+        write_leb(&mut out, size as u64);
+        out.extend_from_slice(contents);
+    }
+    Ok(out)
+}
+
+fn check_initial(initial: u64, max_pages: u64, limit: u64) -> anyhow::Result<()> {
+    if initial > max_pages {
+        return Err(anyhow!(
+            "out of memory budget: module needs {} bytes of linear memory, limit is {}",
+            initial * WASM_PAGE_SIZE,
+            limit
+        )
+        .context(ErrorClass::OutOfMemoryBudget));
+    }
+    Ok(())
+}
+
+fn limits(r: &mut Reader) -> anyhow::Result<(u8, u64, Option<u64>)> {
+    let flags = r.u8()?;
+    if flags & LIMITS_MEMORY64 != 0 {
+        bail!("64-bit memories are not supported");
+    }
+    let initial = r.leb()?;
+    let max = if flags & LIMITS_HAS_MAX != 0 {
+        Some(r.leb()?)
+    } else {
+        None
+    };
+    Ok((flags, initial, max))
+}
+
+fn write_leb(out: &mut Vec<u8>, mut value: u64) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            out.push(byte);
+            return;
+        }
+        out.push(byte | 0x80);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_limit_memory() {
+        // One memory, 2 pages initial, no maximum.
+        let wasm = b"\0asm\x01\0\0\0\x05\x03\x01\x00\x02";
+        let limited = limit_memory(wasm, 4 * WASM_PAGE_SIZE).unwrap();
+        assert_eq!(limited, b"\0asm\x01\0\0\0\x05\x04\x01\x01\x02\x04");
+
+        let e = limit_memory(wasm, WASM_PAGE_SIZE).unwrap_err();
+        assert_eq!(ErrorClass::of(&e), Some(ErrorClass::OutOfMemoryBudget));
+    }
+}
@@ -0,0 +1,126 @@
+use serde::Serialize;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use ya_emscripten_meta::MountPoint;
+
+/// Problems with the manifest's mount declarations noticed after a run.
+#[derive(Serialize, Debug)]
+#[serde(tag = "warning", rename_all = "kebab-case")]
+pub enum MountWarning {
+    /// Writable mount that the program never wrote to.
+    UnwrittenMount { path: String },
+}
+
+/// Latest modification time of a directory tree. Symlinks are not
+/// followed, so dangling ones count with their own mtime.
+pub fn last_modified(path: &Path) -> io::Result<SystemTime> {
+    let mut latest = fs::symlink_metadata(path)?.modified()?;
+    for entry in fs::read_dir(path)? {
+        let entry = entry?;
+        let modified = if entry.file_type()?.is_dir() {
+            last_modified(&entry.path())?
+        } else {
+            fs::symlink_metadata(entry.path())?.modified()?
+        };
+        latest = latest.max(modified);
+    }
+    Ok(latest)
+}
+
+/// Total size of regular files in a directory tree.
+pub fn tree_size(path: &Path) -> io::Result<u64> {
+    let mut size = 0;
+    for entry in fs::read_dir(path)? {
+        let entry = entry?;
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            size += tree_size(&entry.path())?;
+        } else if file_type.is_file() {
+            size += entry.metadata()?.len();
+        }
+    }
+    Ok(size)
+}
+
+/// Number of files and directories in a tree, not counting `path` itself.
+/// Every directory is added to `per_dir` with its number of direct entries.
+pub fn count_entries(path: &Path, per_dir: &mut Vec<(PathBuf, u64)>) -> io::Result<u64> {
+    let (mut total, mut direct) = (0, 0);
+    for entry in fs::read_dir(path)? {
+        let entry = entry?;
+        direct += 1;
+        total += 1;
+        if entry.file_type()?.is_dir() {
+            total += count_entries(&entry.path(), per_dir)?;
+        }
+    }
+    per_dir.push((path.to_owned(), direct));
+    Ok(total)
+}
+
+/// Flushes all files and directories of a tree to stable storage.
+pub fn sync_tree(path: &Path) -> io::Result<()> {
+    for entry in fs::read_dir(path)? {
+        let entry = entry?;
+        if entry.file_type()?.is_dir() {
+            sync_tree(&entry.path())?;
+        } else if entry.file_type()?.is_file() {
+            fs::File::open(entry.path())?.sync_all()?;
+        }
+    }
+    sync_dir(path)
+}
+
+#[cfg(unix)]
+fn sync_dir(path: &Path) -> io::Result<()> {
+    fs::File::open(path)?.sync_all()
+}
+
+#[cfg(not(unix))]
+fn sync_dir(_path: &Path) -> io::Result<()> {
+    Ok(())
+}
+
+/// Tracks writes to the writable mounts of a single run.
+pub struct MountActivity<'a> {
+    workdir: &'a Path,
+    before: Vec<(&'a str, &'a MountPoint, SystemTime)>,
+}
+
+impl<'a> MountActivity<'a> {
+    pub fn snapshot(workdir: &'a Path, mounts: &'a [(String, MountPoint)]) -> io::Result<Self> {
+        let before = mounts
+            .iter()
+            .filter(|(_, mount_point)| !matches!(mount_point, MountPoint::Ro(_)))
+            .map(|(dir, mount_point)| -> io::Result<_> {
+                Ok((
+                    dir.as_str(),
+                    mount_point,
+                    last_modified(&workdir.join(dir))?,
+                ))
+            })
+            .collect::<io::Result<Vec<_>>>()?;
+        Ok(MountActivity { workdir, before })
+    }
+
+    /// Host directories of the writable mounts.
+    pub fn writable_dirs(&self) -> impl Iterator<Item = PathBuf> + '_ {
+        self.before
+            .iter()
+            .map(move |(dir, _, _)| self.workdir.join(dir))
+    }
+
+    pub fn warnings(&self) -> io::Result<Vec<MountWarning>> {
+        let mut warnings = Vec::new();
+        for (dir, mount_point, before) in &self.before {
+            if last_modified(&self.workdir.join(dir))? <= *before {
+                warnings.push(MountWarning::UnwrittenMount {
+                    path: mount_point.path().to_string(),
+                });
+            }
+        }
+        Ok(warnings)
+    }
+}
@@ -0,0 +1,224 @@
+use crate::{archive, strict, tarball};
+use anyhow::bail;
+use std::fs;
+use std::path::{Path, PathBuf};
+use ya_emscripten_meta::Manifest;
+
+pub const MANIFEST_NAME: &str = "gu-package.json";
+/// Alternative to `MANIFEST_NAME` for hand-written manifests, used when
+/// there's no JSON one.
+pub const TOML_MANIFEST_NAME: &str = "gu-package.toml";
+
+/// Task package, either a zip archive or an already unpacked directory.
+/// `.tar.gz` packages are opened as their unpacked copy.
+#[derive(Debug, Clone)]
+pub enum Package {
+    Archive(PathBuf),
+    Directory(PathBuf),
+}
+
+/// Auxiliary data-only package mounted read-only at `guest_path`.
+#[derive(Debug, Clone)]
+pub struct DataPackage {
+    pub guest_path: String,
+    pub package: Package,
+}
+
+impl std::str::FromStr for DataPackage {
+    type Err = anyhow::Error;
+
+    /// Parses `<guest-path>=<package-path>`.
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        let mut parts = s.splitn(2, '=');
+        match (parts.next(), parts.next()) {
+            (Some(guest_path), Some(path)) if guest_path.starts_with('/') => {
+                let package = Package::open(path)?;
+                package.check_entries()?;
+                Ok(DataPackage {
+                    guest_path: guest_path.to_string(),
+                    package,
+                })
+            }
+            _ => bail!("expected <absolute guest path>=<package path>, got {:?}", s),
+        }
+    }
+}
+
+impl Package {
+    pub fn open(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        if fs::metadata(path)?.is_dir() {
+            Ok(Package::Directory(path.to_owned()))
+        } else if tarball::is_tar_gz(path)? {
+            Ok(Package::Directory(tarball::unpacked(path)?))
+        } else {
+            Ok(Package::Archive(path.to_owned()))
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        match self {
+            Package::Archive(path) => path,
+            Package::Directory(path) => path,
+        }
+    }
+
+    /// Loads the manifest and normalizes its path separators, warning about
+    /// every path that had to be fixed.
+    pub fn load_manifest(&self) -> anyhow::Result<Manifest> {
+        let mut m = self.load_raw_manifest()?;
+        for fix in m.sanitize() {
+            strict::fallback(
+                "backslash-path",
+                format!(
+                    "{}: converted {:?} to {:?}",
+                    fix.field, fix.original, fix.fixed
+                ),
+            )?;
+        }
+        Ok(m)
+    }
+
+    /// Name of the manifest entry, `gu-package.json` or `gu-package.toml`.
+    pub fn manifest_entry(&self) -> anyhow::Result<Option<&'static str>> {
+        for name in [MANIFEST_NAME, TOML_MANIFEST_NAME].iter() {
+            if self.has_entry(name)? {
+                return Ok(Some(name));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Loads `gu-package.json`, or `gu-package.toml`, as is. For archives
+    /// without either, falls back to the zip comment and then to an
+    /// `<image>.json` sidecar file.
+    pub fn load_raw_manifest(&self) -> anyhow::Result<Manifest> {
+        if let Some(name) = self.manifest_entry()? {
+            return parse_manifest(name, &self.read_entry(name)?);
+        }
+        if let Package::Archive(path) = self {
+            let comment = archive::with_archive(path, |a| Ok(a.comment().to_vec()))?;
+            if !String::from_utf8_lossy(&comment).trim().is_empty() {
+                strict::fallback(
+                    "manifest-in-comment",
+                    format!("{}: manifest read from the zip comment", path.display()),
+                )?;
+                return Ok(serde_json::from_slice(&comment)?);
+            }
+            let mut sidecar = path.clone().into_os_string();
+            sidecar.push(".json");
+            if Path::new(&sidecar).exists() {
+                strict::fallback(
+                    "sidecar-manifest",
+                    format!("{}: manifest read from {:?}", path.display(), sidecar),
+                )?;
+                return Ok(serde_json::from_slice(&fs::read(sidecar)?)?);
+            }
+        }
+        bail!("{} not found in {}", MANIFEST_NAME, self.path().display())
+    }
+
+    /// Checks archive member names, see `archive::check_entries`, or the
+    /// symlinks of a directory, see `check_tree`.
+    pub fn check_entries(&self) -> anyhow::Result<()> {
+        match self {
+            Package::Archive(path) => archive::with_archive(path, archive::check_entries),
+            Package::Directory(path) => check_tree(path, &fs::canonicalize(path)?),
+        }
+    }
+
+    pub fn has_entry(&self, name: impl AsRef<Path>) -> anyhow::Result<bool> {
+        let name = name.as_ref();
+        match self {
+            Package::Archive(path) => archive::with_archive(path, |a| archive::has_entry(a, name)),
+            Package::Directory(path) => Ok(path.join(name).is_file()),
+        }
+    }
+
+    /// Reads whole entry. `name` is relative to the package root.
+    pub fn read_entry(&self, name: impl AsRef<Path>) -> anyhow::Result<Vec<u8>> {
+        let name = name.as_ref();
+        match self {
+            Package::Archive(path) => archive::with_archive(path, |a| archive::read_entry(a, name)),
+            Package::Directory(path) => Ok(fs::read(path.join(name))?),
+        }
+    }
+}
+
+/// Rejects symlinks in an unpacked package leading outside of `root`, and
+/// anything that is not a file, directory or symlink.
+fn check_tree(dir: &Path, root: &Path) -> anyhow::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let path = entry.path();
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            check_tree(&path, root)?;
+        } else if file_type.is_symlink() {
+            match fs::canonicalize(&path) {
+                Ok(target) if target.starts_with(root) => (),
+                _ => bail!("{} links outside of the package", path.display()),
+            }
+        } else if !file_type.is_file() {
+            bail!("{} is not a regular file", path.display());
+        }
+    }
+    Ok(())
+}
+
+fn parse_manifest(name: &str, bytes: &[u8]) -> anyhow::Result<Manifest> {
+    if name == TOML_MANIFEST_NAME {
+        Ok(toml::from_slice(bytes)?)
+    } else {
+        Ok(serde_json::from_slice(bytes)?)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_toml_manifest() {
+        let json = br#"{
+            "id": "test.golem.network/ls",
+            "name": "ls",
+            "runtime": "emscripten",
+            "entry-points": [ { "id": "ls", "wasm-path": "ls.wasm", "args-prefix": ["-l"] } ],
+            "mount-points": [ { "rw": "/out" } ]
+        }"#;
+        let toml = br#"
+            id = "test.golem.network/ls"
+            name = "ls"
+            runtime = "emscripten"
+
+            # Comments are the point of TOML manifests.
+            [[entry-points]]
+            id = "ls"
+            wasm-path = "ls.wasm"
+            args-prefix = ["-l"]
+
+            [[mount-points]]
+            rw = "/out"
+        "#;
+        assert_eq!(
+            parse_manifest(TOML_MANIFEST_NAME, toml).unwrap(),
+            parse_manifest(MANIFEST_NAME, json).unwrap()
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_check_tree() {
+        let root = std::env::temp_dir().join(format!("pkg-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(root.join("bin")).unwrap();
+        fs::write(root.join("bin/x.wasm"), b"").unwrap();
+        std::os::unix::fs::symlink("x.wasm", root.join("bin/y.wasm")).unwrap();
+        let package = Package::Directory(root.clone());
+        assert!(package.check_entries().is_ok());
+
+        std::os::unix::fs::symlink("/etc/passwd", root.join("bin/x.js")).unwrap();
+        assert!(package.check_entries().is_err());
+        fs::remove_dir_all(root).unwrap();
+    }
+}
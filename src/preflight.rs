@@ -0,0 +1,58 @@
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File};
+use std::io;
+use std::path::Path;
+
+const PROBE_DIR: &str = ".preflight";
+
+/// Capabilities of the filesystem holding the workdir.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "kebab-case")]
+pub struct FsCapabilities {
+    /// 255 byte file names can be created.
+    pub long_names: bool,
+    pub hardlinks: bool,
+    /// `None` when sparseness can't be checked on this platform.
+    pub sparse_files: Option<bool>,
+}
+
+impl FsCapabilities {
+    /// Probes the filesystem by creating (and removing) scratch files in `workdir`.
+    pub fn probe(workdir: &Path) -> io::Result<Self> {
+        let dir = workdir.join(PROBE_DIR);
+        if dir.exists() {
+            // Left behind by an interrupted deploy.
+            fs::remove_dir_all(&dir)?;
+        }
+        fs::create_dir(&dir)?;
+        let caps = FsCapabilities {
+            long_names: File::create(dir.join("n".repeat(255))).is_ok(),
+            hardlinks: probe_hardlinks(&dir),
+            sparse_files: probe_sparse_files(&dir),
+        };
+        fs::remove_dir_all(&dir)?;
+        Ok(caps)
+    }
+}
+
+fn probe_hardlinks(dir: &Path) -> bool {
+    let src = dir.join("link-src");
+    File::create(&src).is_ok() && fs::hard_link(&src, dir.join("link-dst")).is_ok()
+}
+
+#[cfg(unix)]
+fn probe_sparse_files(dir: &Path) -> Option<bool> {
+    use std::os::unix::fs::MetadataExt;
+    const SPARSE_PROBE_LEN: u64 = 1024 * 1024;
+
+    let path = dir.join("sparse");
+    let file = File::create(&path).ok()?;
+    file.set_len(SPARSE_PROBE_LEN).ok()?;
+    let allocated = file.metadata().ok()?.blocks() * 512;
+    Some(allocated < SPARSE_PROBE_LEN)
+}
+
+#[cfg(not(unix))]
+fn probe_sparse_files(_dir: &Path) -> Option<bool> {
+    None
+}
@@ -0,0 +1,102 @@
+use crate::glue::CONFIG_VAR;
+use std::collections::BTreeMap;
+
+/// JS expression for the glue's `FS`, as captured by `env_prelude`. The
+/// hooks can't use the global `Module`, which modularized glue rebinds to
+/// its factory when it has the default export name.
+fn fs_expr() -> String {
+    format!("{}['__yaFS']", CONFIG_VAR)
+}
+
+/// JS evaluated before the emscripten glue. Keeps the `Module` object in
+/// `CONFIG_VAR`, captures `FS` for the other hooks and sets environment
+/// variables from a `preRun` hook, when the glue's `ENV` object already
+/// exists. Modularized glue keeps `ENV` and `FS` private, so there they
+/// have to be exported for the hooks to work.
+pub fn env_prelude(env: &BTreeMap<String, String>) -> String {
+    let mut js = format!(
+        "var Module = typeof Module !== 'undefined' ? Module : {{}};\n\
+         var {config} = Module;\n\
+         Module['preRun'] = [].concat(Module['preRun'] || [], function (module) {{\n\
+         \x20 {fs} = typeof FS !== 'undefined' ? FS : module && module['FS'];\n\
+         \x20 var env = typeof ENV !== 'undefined' ? ENV : module && module['ENV'];\n\
+         \x20 if (!env) return;\n",
+        config = CONFIG_VAR,
+        fs = fs_expr(),
+    );
+    for (key, value) in env {
+        // JSON strings are valid JS string literals.
+        js.push_str(&format!(
+            "  env[{}] = {};\n",
+            serde_json::Value::from(key.as_str()),
+            serde_json::Value::from(value.as_str())
+        ));
+    }
+    js.push_str("});\n");
+    js
+}
+
+/// JS recording the program's exit code in the file at `guest_path`, from
+/// emscripten's `onExit` hook.
+pub fn exit_code_prelude(guest_path: &str) -> String {
+    format!(
+        "Module['onExit'] = function (code) {{\n\
+         \x20 {}.writeFile({}, String(code));\n\
+         }};\n",
+        fs_expr(),
+        serde_json::Value::from(guest_path)
+    )
+}
+
+/// JS creating the file at `guest_path` from emscripten's
+/// `onRuntimeInitialized` hook, which runs after the module is instantiated
+/// and its static constructors have run. Glue that sets its own hook
+/// replaces this one.
+pub fn started_prelude(guest_path: &str) -> String {
+    format!(
+        "Module['onRuntimeInitialized'] = function () {{\n\
+         \x20 {}.writeFile({}, '');\n\
+         }};\n",
+        fs_expr(),
+        serde_json::Value::from(guest_path)
+    )
+}
+
+/// JS feeding the program's standard input from the file at `guest_path`,
+/// read whole on first use. Needs the `FS` captured by `env_prelude`.
+pub fn stdin_prelude(guest_path: &str) -> String {
+    format!(
+        "Module['stdin'] = (function () {{\n\
+         \x20 var data = null, pos = 0;\n\
+         \x20 return function () {{\n\
+         \x20   if (data === null) data = {}.readFile({});\n\
+         \x20   return pos < data.length ? data[pos++] : null;\n\
+         \x20 }};\n\
+         }})();\n",
+        fs_expr(),
+        serde_json::Value::from(guest_path)
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_hooks_use_captured_fs() {
+        let js = [
+            env_prelude(&BTreeMap::new()),
+            exit_code_prelude("/home/golem/.exit-code"),
+            started_prelude("/home/golem/.started"),
+            stdin_prelude("/runtime/stdin"),
+        ]
+        .concat();
+        assert!(js.contains("var __yaModuleConfig = Module;\n"));
+        assert!(js.contains(
+            "__yaModuleConfig['__yaFS'] = typeof FS !== 'undefined' ? FS : module && module['FS'];"
+        ));
+        // Hooks run after modularized glue may have rebound `Module`.
+        assert!(!js.contains("Module['FS']"));
+        assert_eq!(js.matches("__yaModuleConfig['__yaFS'].").count(), 3);
+    }
+}
@@ -0,0 +1,58 @@
+/// Scheduling class of the engine thread.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Priority {
+    Realtime,
+    Normal,
+    Background,
+}
+
+impl std::str::FromStr for Priority {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        match s {
+            "realtime" => Ok(Priority::Realtime),
+            "normal" => Ok(Priority::Normal),
+            "background" => Ok(Priority::Background),
+            _ => anyhow::bail!(
+                "unknown priority: {}, expected realtime, normal or background",
+                s
+            ),
+        }
+    }
+}
+
+impl Priority {
+    fn nice(self) -> i32 {
+        match self {
+            Priority::Realtime => -10,
+            Priority::Normal => 0,
+            Priority::Background => 10,
+        }
+    }
+
+    /// Sets the nice value of the calling thread. Raising priority usually
+    /// needs CAP_SYS_NICE; failures are reported as warnings.
+    #[cfg(target_os = "linux")]
+    pub fn apply_to_current_thread(self) {
+        // Safety: gettid and setpriority have no memory safety preconditions.
+        let ret = unsafe {
+            let tid = libc::syscall(libc::SYS_gettid) as libc::id_t;
+            libc::setpriority(libc::PRIO_PROCESS, tid, self.nice())
+        };
+        if ret != 0 {
+            log::warn!(
+                "can't set {:?} priority: {}",
+                self,
+                std::io::Error::last_os_error()
+            );
+        }
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    pub fn apply_to_current_thread(self) {
+        if self != Priority::Normal {
+            log::warn!("thread priorities are only supported on linux");
+        }
+    }
+}
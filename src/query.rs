@@ -0,0 +1,76 @@
+//! Minimal jq-like path expressions: `.a.b`, `.a[0]`, `.["key with space"]`.
+
+use anyhow::{anyhow, bail};
+use serde_json::Value;
+
+static NULL: Value = Value::Null;
+
+#[derive(Debug, PartialEq)]
+enum Segment {
+    Key(String),
+    Index(usize),
+}
+
+fn parse(expr: &str) -> anyhow::Result<Vec<Segment>> {
+    let expr = expr.trim();
+    if !expr.starts_with('.') {
+        bail!("expression must start with '.': {}", expr);
+    }
+    let mut segments = Vec::new();
+    let mut rest = &expr[1..];
+    while !rest.is_empty() {
+        if let Some(tail) = rest.strip_prefix('[') {
+            let end = tail
+                .find(']')
+                .ok_or_else(|| anyhow!("unclosed '[' in {}", expr))?;
+            let inner = &tail[..end];
+            segments.push(if inner.starts_with('"') {
+                Segment::Key(serde_json::from_str(inner)?)
+            } else {
+                Segment::Index(inner.parse()?)
+            });
+            rest = &tail[end + 1..];
+        } else {
+            let key = rest.strip_prefix('.').unwrap_or(rest);
+            let end = key.find(&['.', '['][..]).unwrap_or(key.len());
+            if end == 0 {
+                bail!("empty key in {}", expr);
+            }
+            segments.push(Segment::Key(key[..end].to_string()));
+            rest = &key[end..];
+        }
+    }
+    Ok(segments)
+}
+
+/// Evaluates `expr` over `value`. Missing keys and indices give `null`, like jq.
+pub fn eval<'a>(expr: &str, value: &'a Value) -> anyhow::Result<&'a Value> {
+    let mut current = value;
+    for segment in parse(expr)? {
+        current = match (&segment, current) {
+            (Segment::Key(key), Value::Object(map)) => map.get(key).unwrap_or(&NULL),
+            (Segment::Index(idx), Value::Array(items)) => items.get(*idx).unwrap_or(&NULL),
+            (_, Value::Null) => &NULL,
+            (segment, value) => bail!("can't apply {:?} to {}", segment, value),
+        };
+    }
+    Ok(current)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_eval() {
+        let value = serde_json::json!({"a": {"b": [1, {"c d": true}]}});
+        assert_eq!(eval(".", &value).unwrap(), &value);
+        assert_eq!(eval(".a.b[0]", &value).unwrap(), &serde_json::json!(1));
+        assert_eq!(
+            eval(".a.b[1][\"c d\"]", &value).unwrap(),
+            &Value::Bool(true)
+        );
+        assert_eq!(eval(".x.y", &value).unwrap(), &Value::Null);
+        assert!(eval(".a.b.c", &value).is_err());
+    }
+}
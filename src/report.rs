@@ -0,0 +1,243 @@
+//! Exec reports, serialized like the `ProcessStatus` and `ErrorResponse`
+//! messages of ya-runtime-api so supervisors of sdk based runtimes can
+//! read them unchanged.
+//!
+//! Machine-readable documents printed or stored by the runtime carry
+//! `schema_version`, keep their fields in declaration order (maps are
+//! sorted), use RFC 3339 UTC timestamps and integer byte counts.
+
+use crate::scan::ScanResult;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+pub const RESULTS_DIR: &str = "results";
+
+/// Bumped on incompatible changes to any machine-readable output.
+pub const SCHEMA_VERSION: u32 = 1;
+
+pub fn timestamp(time: SystemTime) -> String {
+    humantime::format_rfc3339_millis(time).to_string()
+}
+
+#[derive(Serialize, Debug)]
+pub struct ProcessStatus {
+    pub pid: u64,
+    pub running: bool,
+    pub return_code: i32,
+    /// Last `STATUS_OUTPUT_TAIL` bytes of the captured stdout.
+    pub stdout: Vec<u8>,
+    /// Last `STATUS_OUTPUT_TAIL` bytes of the captured stderr.
+    pub stderr: Vec<u8>,
+}
+
+/// Bytes of each captured output stream kept in `ProcessStatus`, the full
+/// output stays in `<exec-id>/`.
+pub const STATUS_OUTPUT_TAIL: u64 = 4096;
+
+/// Failure classes with their own process exit codes. Attached to errors
+/// with `anyhow::Context`; unclassified errors exit with 1, or with
+/// `Io`'s code when caused by an I/O error.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ErrorClass {
+    BadImage,
+    MissingEntryPoint,
+    Engine,
+    Io,
+    IncompatibleRuntime,
+    Timeout,
+    /// The program didn't reach `main` in time, see `RunOptions::startup_timeout`.
+    StartupTimeout,
+    OutOfMemoryBudget,
+    OutputQuotaExceeded,
+    /// Writable mounts hold more files than `RunOptions::max_files`.
+    FileCountExceeded,
+    /// Fallback refused in strict mode, see `strict`.
+    StrictMode,
+    /// The program exited with this non-zero code. The process exits with
+    /// `PROGRAM_EXIT_CODE`, the program's code is in the exec report's
+    /// `return_code` and the error's `program-exit-code` context.
+    ProgramExit(i32),
+}
+
+/// Exit code of the runtime for a program that exited with a non-zero
+/// code, which could otherwise collide with the other classes or be
+/// truncated to 8 bits.
+pub const PROGRAM_EXIT_CODE: i32 = 2;
+
+impl ErrorClass {
+    pub fn exit_code(self) -> i32 {
+        match self {
+            ErrorClass::BadImage => 3,
+            ErrorClass::MissingEntryPoint => 4,
+            ErrorClass::Engine => 5,
+            ErrorClass::Io => 6,
+            ErrorClass::IncompatibleRuntime => 7,
+            ErrorClass::Timeout => 8,
+            ErrorClass::OutOfMemoryBudget => 9,
+            ErrorClass::OutputQuotaExceeded => 10,
+            ErrorClass::StrictMode => 11,
+            ErrorClass::StartupTimeout => 12,
+            ErrorClass::FileCountExceeded => 13,
+            ErrorClass::ProgramExit(_) => PROGRAM_EXIT_CODE,
+        }
+    }
+
+    pub fn of(e: &anyhow::Error) -> Option<Self> {
+        if let Some(class) = e.downcast_ref::<ErrorClass>() {
+            return Some(*class);
+        }
+        if e.chain().any(|cause| cause.is::<io::Error>()) {
+            return Some(ErrorClass::Io);
+        }
+        None
+    }
+}
+
+impl fmt::Display for ErrorClass {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            ErrorClass::BadImage => "bad image",
+            ErrorClass::MissingEntryPoint => "missing entry point",
+            ErrorClass::Engine => "engine failure",
+            ErrorClass::Io => "i/o error",
+            ErrorClass::IncompatibleRuntime => "incompatible runtime version",
+            ErrorClass::Timeout => "timeout",
+            ErrorClass::StartupTimeout => "startup timeout",
+            ErrorClass::OutOfMemoryBudget => "out of memory budget",
+            ErrorClass::OutputQuotaExceeded => "output quota exceeded",
+            ErrorClass::FileCountExceeded => "file count exceeded",
+            ErrorClass::StrictMode => "strict mode violation",
+            ErrorClass::ProgramExit(code) => {
+                return write!(f, "program exited with code {}", code);
+            }
+        };
+        f.write_str(name)
+    }
+}
+
+/// Exit code of the runtime process for a failed command.
+pub fn exit_code(e: &anyhow::Error) -> i32 {
+    ErrorClass::of(e).map_or(1, ErrorClass::exit_code)
+}
+
+#[derive(Serialize, Debug)]
+pub struct ErrorResponse {
+    pub code: i32,
+    pub message: String,
+    pub context: BTreeMap<String, String>,
+}
+
+impl ErrorResponse {
+    pub fn from_error(e: &anyhow::Error) -> Self {
+        let mut context = BTreeMap::new();
+        for (i, cause) in e.chain().skip(1).enumerate() {
+            context.insert(format!("cause.{}", i), cause.to_string());
+        }
+        if let Some(ErrorClass::ProgramExit(code)) = ErrorClass::of(e) {
+            context.insert("program-exit-code".to_string(), code.to_string());
+        }
+        ErrorResponse {
+            code: exit_code(e),
+            message: e.to_string(),
+            context,
+        }
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub struct ExecReport {
+    pub schema_version: u32,
+    pub runtime_version: &'static str,
+    pub exec_id: String,
+    pub entry_point: String,
+    pub stack_size: usize,
+    /// SHA-256 of the `--args-file` the arguments were read from.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub args_sha256: Option<String>,
+    pub started_at: String,
+    pub finished_at: String,
+    /// Usage counters for billing, named like ya-provider's
+    /// `golem.usage.*` properties.
+    pub usage: BTreeMap<String, f64>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub scans: Vec<ScanResult>,
+    pub status: ProcessStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<ErrorResponse>,
+}
+
+impl ExecReport {
+    pub fn new(
+        exec_id: String,
+        entry_point: String,
+        stack_size: usize,
+        started_at: SystemTime,
+        result: &anyhow::Result<i32>,
+    ) -> Self {
+        let (return_code, error) = match result {
+            Ok(code) => (*code, None),
+            Err(e) => {
+                let return_code = match ErrorClass::of(e) {
+                    Some(ErrorClass::ProgramExit(code)) => code,
+                    _ => 1,
+                };
+                (return_code, Some(ErrorResponse::from_error(e)))
+            }
+        };
+        ExecReport {
+            schema_version: SCHEMA_VERSION,
+            runtime_version: crate::RUNTIME_VERSION,
+            exec_id,
+            entry_point,
+            stack_size,
+            args_sha256: None,
+            started_at: timestamp(started_at),
+            finished_at: timestamp(SystemTime::now()),
+            usage: BTreeMap::new(),
+            scans: Vec::new(),
+            status: ProcessStatus {
+                pid: u64::from(std::process::id()),
+                running: false,
+                return_code,
+                stdout: Vec::new(),
+                stderr: Vec::new(),
+            },
+            error,
+        }
+    }
+
+    /// Writes report to `results/<exec-id>.json` in the workdir.
+    pub fn save(&self, workdir: &Path) -> anyhow::Result<PathBuf> {
+        let dir = workdir.join(RESULTS_DIR);
+        fs::create_dir_all(&dir)?;
+        let path = dir.join(format!("{}.json", self.exec_id));
+        fs::write(&path, serde_json::to_vec_pretty(self)?)?;
+        Ok(path)
+    }
+}
+
+/// Most recently written exec report of a workdir.
+pub fn latest(workdir: &Path) -> anyhow::Result<Option<PathBuf>> {
+    let dir = workdir.join(RESULTS_DIR);
+    if !dir.exists() {
+        return Ok(None);
+    }
+    let mut latest = None;
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let modified = entry.metadata()?.modified()?;
+        let newer = match &latest {
+            Some((time, _)) => modified > *time,
+            None => true,
+        };
+        if newer {
+            latest = Some((modified, entry.path()));
+        }
+    }
+    Ok(latest.map(|(_, path)| path))
+}
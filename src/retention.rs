@@ -0,0 +1,99 @@
+use crate::mounts;
+use crate::report::RESULTS_DIR;
+use std::fs;
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+use structopt::StructOpt;
+
+/// Limits for the exec reports kept in a workdir.
+#[derive(StructOpt, Debug, Clone)]
+#[structopt(rename_all = "kebab-case")]
+pub struct Retention {
+    /// Remove exec reports older than this many seconds
+    #[structopt(long)]
+    pub results_max_age: Option<u64>,
+    /// Keep at most this many bytes of exec reports and captured output
+    #[structopt(long)]
+    pub results_max_bytes: Option<u64>,
+}
+
+impl Retention {
+    /// Removes the oldest reports, with their captured output, over the
+    /// limits. The newest one is always kept.
+    pub fn prune(&self, workdir: &Path) -> anyhow::Result<()> {
+        if self.results_max_age.is_none() && self.results_max_bytes.is_none() {
+            return Ok(());
+        }
+        let dir = workdir.join(RESULTS_DIR);
+        if !dir.exists() {
+            return Ok(());
+        }
+
+        let mut reports = Vec::new();
+        for entry in fs::read_dir(dir)? {
+            let entry = entry?;
+            let meta = entry.metadata()?;
+            if meta.is_file() {
+                let path = entry.path();
+                let output = path.file_stem().map(|exec_id| workdir.join(exec_id));
+                let mut size = meta.len();
+                if let Some(output) = output.as_ref().filter(|output| output.is_dir()) {
+                    size += mounts::tree_size(output)?;
+                }
+                reports.push((meta.modified()?, size, path, output));
+            }
+        }
+        reports.sort_by(|a, b| b.0.cmp(&a.0));
+
+        let now = SystemTime::now();
+        let max_age = self.results_max_age.map(Duration::from_secs);
+        let mut total = 0;
+        for (idx, (modified, size, path, output)) in reports.into_iter().enumerate() {
+            total += size;
+            if idx == 0 {
+                continue;
+            }
+            let too_old = max_age
+                .is_some_and(|max_age| now.duration_since(modified).unwrap_or_default() > max_age);
+            let too_big = self.results_max_bytes.is_some_and(|max| total > max);
+            if too_old || too_big {
+                fs::remove_file(&path)?;
+                if let Some(output) = output.filter(|output| output.is_dir()) {
+                    fs::remove_dir_all(output)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_prune_keeps_newest() {
+        let workdir = std::env::temp_dir().join(format!("retention-{}", uuid::Uuid::new_v4()));
+        let results = workdir.join(RESULTS_DIR);
+        fs::create_dir_all(&results).unwrap();
+        for exec_id in &["old", "new"] {
+            fs::create_dir(workdir.join(exec_id)).unwrap();
+            fs::write(workdir.join(exec_id).join("stdout"), b"output").unwrap();
+            fs::write(results.join(format!("{}.json", exec_id)), b"{}").unwrap();
+            // Apart in modification time.
+            std::thread::sleep(Duration::from_millis(20));
+        }
+
+        let retention = Retention {
+            results_max_age: Some(0),
+            results_max_bytes: Some(1),
+        };
+        retention.prune(&workdir).unwrap();
+        assert!(results.join("new.json").exists());
+        assert!(workdir.join("new").exists());
+        assert!(!results.join("old.json").exists());
+        assert!(!workdir.join("old").exists());
+
+        fs::remove_dir_all(&workdir).unwrap();
+    }
+}
@@ -0,0 +1,69 @@
+//! `/runtime/info.json`, a read-only file describing the current exec to
+//! the program itself.
+
+use crate::report::{timestamp, SCHEMA_VERSION};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use ya_emscripten_meta::MountPoint;
+
+/// Guest directory holding `info.json`.
+pub const RUNTIME_DIR: &str = "/runtime";
+
+/// Name of the program's standard input in `RUNTIME_DIR`, see `--stdin`.
+pub const STDIN_FILE: &str = "stdin";
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "kebab-case")]
+pub struct RuntimeInfo {
+    pub schema_version: u32,
+    pub runtime_version: &'static str,
+    pub exec_id: String,
+    pub entry_point: String,
+    /// Time the run is stopped at, if it has a timeout.
+    pub deadline: Option<String>,
+    pub limits: Limits,
+    /// Mode of every mount, by guest path.
+    pub mounts: BTreeMap<String, &'static str>,
+}
+
+#[derive(Serialize, Debug, Default)]
+#[serde(rename_all = "kebab-case")]
+pub struct Limits {
+    pub timeout_sec: Option<u64>,
+    pub mem_limit: Option<u64>,
+    pub output_quota: Option<u64>,
+    pub max_files: Option<u64>,
+    pub stack_size: usize,
+}
+
+impl RuntimeInfo {
+    pub fn new(exec_id: &str, entry_point: &str, deadline: Option<SystemTime>) -> Self {
+        RuntimeInfo {
+            schema_version: SCHEMA_VERSION,
+            runtime_version: crate::RUNTIME_VERSION,
+            exec_id: exec_id.to_owned(),
+            entry_point: entry_point.to_owned(),
+            deadline: deadline.map(timestamp),
+            limits: Limits::default(),
+            mounts: BTreeMap::new(),
+        }
+    }
+
+    pub fn add_mount(&mut self, mount_point: &MountPoint) {
+        self.mounts
+            .insert(mount_point.path().to_owned(), mount_point.mode());
+    }
+
+    /// Writes `info.json` into `<exec dir>/runtime`, the host directory
+    /// mounted at `RUNTIME_DIR`.
+    pub fn write(&self, exec_dir: &Path) -> io::Result<PathBuf> {
+        let dir = exec_dir.join("runtime");
+        fs::create_dir_all(&dir)?;
+        fs::write(dir.join("info.json"), serde_json::to_vec_pretty(self)?)?;
+        Ok(dir)
+    }
+}
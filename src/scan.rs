@@ -0,0 +1,74 @@
+//! External malware scanner hook, e.g. `clamscan --no-summary {}`.
+
+use serde::Serialize;
+use std::path::Path;
+use std::process::Command;
+
+/// Bytes of scanner output kept in the report.
+const OUTPUT_LIMIT: usize = 4096;
+
+/// Command template; `{}` arguments are replaced by the scanned path, which
+/// is appended when there is none. Exit code 0 means clean.
+#[derive(Debug, Clone)]
+pub struct Scanner {
+    template: Vec<String>,
+}
+
+impl std::str::FromStr for Scanner {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        let template: Vec<String> = s.split_whitespace().map(ToString::to_string).collect();
+        if template.is_empty() {
+            anyhow::bail!("empty scan command");
+        }
+        Ok(Scanner { template })
+    }
+}
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "kebab-case")]
+pub struct ScanResult {
+    /// `input` or `output`.
+    pub target: &'static str,
+    pub path: String,
+    pub exit_code: Option<i32>,
+    pub clean: bool,
+    /// Combined stdout and stderr of the scanner, truncated.
+    pub output: String,
+}
+
+impl Scanner {
+    pub fn scan(&self, target: &'static str, path: &Path) -> anyhow::Result<ScanResult> {
+        let path_arg = path.display().to_string();
+        let args = expand_args(&self.template[1..], &path_arg);
+        let output = Command::new(&self.template[0]).args(&args).output()?;
+
+        let mut text = String::from_utf8_lossy(&output.stdout).into_owned();
+        text.push_str(&String::from_utf8_lossy(&output.stderr));
+        if text.len() > OUTPUT_LIMIT {
+            let mut end = OUTPUT_LIMIT;
+            while !text.is_char_boundary(end) {
+                end -= 1;
+            }
+            text.truncate(end);
+        }
+        Ok(ScanResult {
+            target,
+            path: path_arg,
+            exit_code: output.status.code(),
+            clean: output.status.success(),
+            output: text,
+        })
+    }
+}
+
+/// Replaces `{}` in command arguments with `path`, which is appended when
+/// no argument has a placeholder.
+pub(crate) fn expand_args(template: &[String], path: &str) -> Vec<String> {
+    let mut args: Vec<String> = template.iter().map(|arg| arg.replace("{}", path)).collect();
+    if !template.iter().any(|arg| arg.contains("{}")) {
+        args.push(path.to_string());
+    }
+    args
+}
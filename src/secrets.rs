@@ -0,0 +1,26 @@
+use std::collections::BTreeMap;
+
+/// Reads a JSON object of secret environment variables from an inherited
+/// file descriptor. Secrets are kept in memory only.
+#[cfg(unix)]
+pub fn read_secrets(fd: i32) -> anyhow::Result<BTreeMap<String, String>> {
+    use std::fs::File;
+    use std::os::unix::io::FromRawFd;
+
+    // Closing stdin, stdout or stderr after reading would break the run.
+    if fd <= libc::STDERR_FILENO {
+        anyhow::bail!("--secrets-fd can't be a standard stream, got {}", fd);
+    }
+    // Safety: F_GETFD only inspects the descriptor.
+    if unsafe { libc::fcntl(fd, libc::F_GETFD) } < 0 {
+        anyhow::bail!("--secrets-fd {} is not an open descriptor", fd);
+    }
+    // Safety: the descriptor is handed over by the caller and not used elsewhere.
+    let file = unsafe { File::from_raw_fd(fd) };
+    Ok(serde_json::from_reader(file)?)
+}
+
+#[cfg(not(unix))]
+pub fn read_secrets(_fd: i32) -> anyhow::Result<BTreeMap<String, String>> {
+    anyhow::bail!("--secrets-fd is only supported on unix")
+}
@@ -0,0 +1,46 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// Creates a new directory accessible only by the current user.
+///
+/// Creation is atomic and fails if anything, including a symlink, already
+/// exists at `path`. The result is checked to be a real directory owned by
+/// us, so a concurrent local attacker can't substitute their own.
+pub fn create_private_dir(path: &Path) -> io::Result<()> {
+    let mut builder = fs::DirBuilder::new();
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::DirBuilderExt;
+        builder.mode(0o700);
+    }
+    builder.create(path)?;
+
+    let meta = fs::symlink_metadata(path)?;
+    if !meta.file_type().is_dir() {
+        return Err(io::Error::new(
+            io::ErrorKind::Other,
+            format!("{} was replaced during creation", path.display()),
+        ));
+    }
+    check_owner(path, &meta)
+}
+
+#[cfg(unix)]
+fn check_owner(path: &Path, meta: &fs::Metadata) -> io::Result<()> {
+    use std::os::unix::fs::MetadataExt;
+
+    // Safety: geteuid has no preconditions and can't fail.
+    if meta.uid() != unsafe { libc::geteuid() } {
+        return Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            format!("{} is owned by another user", path.display()),
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn check_owner(_path: &Path, _meta: &fs::Metadata) -> io::Result<()> {
+    Ok(())
+}
@@ -0,0 +1,96 @@
+//! Ed25519 image signatures.
+//!
+//! A detached `--signature` signs the package archive file. A signature
+//! embedded as `gu-package.json.sig` signs the SHA-256 digests of
+//! `gu-package.json` followed by the wasm and js files of every entry
+//! point, in manifest order. Signatures and keys are raw bytes or hex.
+
+use crate::package::{Package, MANIFEST_NAME};
+use crate::{normalize_path, strict};
+use anyhow::{anyhow, bail, Context};
+use ed25519_dalek::{PublicKey, Signature, Verifier};
+use sha2::{Digest, Sha256};
+use std::convert::TryFrom;
+use std::fs;
+use std::path::{Path, PathBuf};
+use structopt::StructOpt;
+use ya_emscripten_meta::Manifest;
+
+pub const SIGNATURE_ENTRY: &str = "gu-package.json.sig";
+
+#[derive(StructOpt, Debug, Clone)]
+#[structopt(rename_all = "kebab-case")]
+pub struct SignaturePolicy {
+    /// Ed25519 public key file images are verified against
+    #[structopt(long, parse(from_os_str), env = "YA_EMSCRIPTEN_PUBLIC_KEY")]
+    pub public_key: Option<PathBuf>,
+    /// Detached signature of the package file
+    #[structopt(long, parse(from_os_str))]
+    pub signature: Option<PathBuf>,
+    /// Reject images without a valid signature
+    #[structopt(long)]
+    pub require_signature: bool,
+}
+
+impl SignaturePolicy {
+    /// Fails when the image's signature is invalid, or missing while required.
+    pub fn check(&self, package: &Package, m: &Manifest) -> anyhow::Result<()> {
+        let key = match &self.public_key {
+            Some(path) => PublicKey::from_bytes(&read_bytes(path, 32)?)
+                .map_err(|e| anyhow!("{}: {}", path.display(), e))?,
+            None if self.require_signature || self.signature.is_some() => {
+                bail!("verifying signatures needs --public-key")
+            }
+            None => return Ok(()),
+        };
+
+        let (message, signature) = match &self.signature {
+            Some(path) => match package {
+                Package::Archive(archive) => (fs::read(archive)?, read_bytes(path, 64)?),
+                Package::Directory(_) => bail!("detached signatures need a package archive"),
+            },
+            None if package.has_entry(SIGNATURE_ENTRY)? => (
+                signed_digests(package, m)?,
+                decode(package.read_entry(SIGNATURE_ENTRY)?, 64)?,
+            ),
+            None if self.require_signature => {
+                bail!("{} is not signed", package.path().display())
+            }
+            None => {
+                let message = format!("{} is not signed", package.path().display());
+                return strict::fallback("unsigned-image", message);
+            }
+        };
+        let signature = Signature::try_from(&signature[..])?;
+        key.verify(&message, &signature)
+            .map_err(|_| anyhow!("invalid signature of {}", package.path().display()))
+    }
+}
+
+pub(crate) fn signed_digests(package: &Package, m: &Manifest) -> anyhow::Result<Vec<u8>> {
+    let manifest = package.manifest_entry()?.unwrap_or(MANIFEST_NAME);
+    let mut message = Sha256::digest(&package.read_entry(manifest)?).to_vec();
+    for ep in m.main.iter().chain(&m.entry_points) {
+        let wasm_path = normalize_path(&ep.wasm_path)?;
+        for path in &[wasm_path.with_extension("js"), wasm_path] {
+            message.extend(Sha256::digest(&package.read_entry(path)?));
+        }
+    }
+    Ok(message)
+}
+
+fn read_bytes(path: &Path, len: usize) -> anyhow::Result<Vec<u8>> {
+    decode(fs::read(path)?, len).with_context(|| format!("{}", path.display()))
+}
+
+/// Accepts `len` raw bytes or their hex encoding.
+fn decode(data: Vec<u8>, len: usize) -> anyhow::Result<Vec<u8>> {
+    if data.len() == len {
+        return Ok(data);
+    }
+    let bytes = hex::decode(String::from_utf8_lossy(&data).trim())?;
+    if bytes.len() != len {
+        bail!("expected {} bytes, got {}", len, bytes.len());
+    }
+    Ok(bytes)
+}
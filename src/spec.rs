@@ -0,0 +1,138 @@
+//! Exec spec passed by the ExeUnit with `--spec`, carrying what the
+//! agreement says about the run. Only the fields below are used, anything
+//! else in the file is ignored.
+//!
+//! Command line options take precedence over the spec, and the spec over
+//! the manifest.
+
+use crate::{normalize_path, strict};
+use anyhow::bail;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(rename_all = "kebab-case")]
+pub struct ExecSpec {
+    /// Program arguments, used when none are given on the command line.
+    #[serde(default)]
+    pub args: Vec<String>,
+    /// Environment variables, overriding the entry point's `env`.
+    #[serde(default)]
+    pub env: BTreeMap<String, String>,
+    /// Host directories mounted on top of the manifest's mounts.
+    #[serde(default)]
+    pub mounts: Vec<ExtraMount>,
+    #[serde(default)]
+    pub limits: SpecLimits,
+    /// Entry point run by `open` instead of the manifest's `main`.
+    pub entry_point: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "kebab-case")]
+pub struct ExtraMount {
+    pub host_path: PathBuf,
+    pub guest_path: String,
+    #[serde(default)]
+    pub writable: bool,
+}
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(rename_all = "kebab-case")]
+pub struct SpecLimits {
+    pub timeout_sec: Option<u64>,
+    pub startup_timeout_sec: Option<u64>,
+    pub mem_limit: Option<u64>,
+    pub output_quota: Option<u64>,
+    pub max_files: Option<u64>,
+    pub stack_size: Option<usize>,
+}
+
+impl ExecSpec {
+    /// Loads the spec. A missing or unparsable spec is a fallback to an
+    /// empty one, see `strict`, but a parsed spec with unusable mounts is
+    /// an error.
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        if !path.exists() {
+            strict::fallback(
+                "missing-spec",
+                format!("spec {} does not exist, ignoring it", path.display()),
+            )?;
+            return Ok(ExecSpec::default());
+        }
+        let spec: ExecSpec = match serde_json::from_slice(&fs::read(path)?) {
+            Ok(spec) => spec,
+            Err(e) => {
+                strict::fallback(
+                    "unreadable-spec",
+                    format!("spec {} is not usable, ignoring it: {}", path.display(), e),
+                )?;
+                return Ok(ExecSpec::default());
+            }
+        };
+        spec.check_mounts()?;
+        Ok(spec)
+    }
+
+    /// Maps a guest path inside one of the spec's mounts to its host path.
+    /// These are mounted last, so they shadow the image's mounts.
+    pub fn resolve(&self, guest_path: &str) -> anyhow::Result<Option<PathBuf>> {
+        let guest = normalize_path(guest_path)?;
+        for mount in self.mounts.iter().rev() {
+            let mount_path = normalize_path(&mount.guest_path)?;
+            if let Ok(rest) = guest.strip_prefix(&mount_path) {
+                return Ok(Some(mount.host_path.join(rest)));
+            }
+        }
+        Ok(None)
+    }
+
+    fn check_mounts(&self) -> anyhow::Result<()> {
+        for mount in &self.mounts {
+            if !mount.guest_path.starts_with('/') {
+                bail!("spec mount {:?} is not an absolute path", mount.guest_path);
+            }
+            if !mount.host_path.is_dir() {
+                bail!(
+                    "spec mount {}: {} is not a directory",
+                    mount.guest_path,
+                    mount.host_path.display()
+                );
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_spec() {
+        let spec: ExecSpec = serde_json::from_str(
+            r#"{
+                "args": ["-v"],
+                "mounts": [ { "host-path": "/tmp", "guest-path": "/scratch", "writable": true } ],
+                "limits": { "timeout-sec": 60 },
+                "entry-point": "render",
+                "agreement-id": "ignored"
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(spec.args, ["-v"]);
+        assert!(spec.mounts[0].writable);
+        assert_eq!(spec.limits.timeout_sec, Some(60));
+        assert_eq!(spec.limits.mem_limit, None);
+        assert_eq!(spec.entry_point.as_deref(), Some("render"));
+        spec.check_mounts().unwrap();
+
+        let spec: ExecSpec = serde_json::from_str(
+            r#"{ "mounts": [ { "host-path": "/tmp", "guest-path": "scratch" } ] }"#,
+        )
+        .unwrap();
+        assert!(spec.check_mounts().is_err());
+    }
+}
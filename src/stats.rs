@@ -0,0 +1,141 @@
+//! Per image and entry point run statistics, kept in the user's local data
+//! directory so they survive workdir teardown.
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fs::{self, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::PathBuf;
+use std::time::Duration;
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "kebab-case")]
+pub struct RunRecord {
+    pub entry_point: String,
+    pub success: bool,
+    pub duration_ms: u64,
+    /// Peak resident set size of the runtime process.
+    pub max_rss_kb: u64,
+    /// Total size of writable mounts after the run.
+    pub output_bytes: u64,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "kebab-case")]
+pub struct Summary {
+    pub runs: usize,
+    pub failures: usize,
+    pub duration_ms: Percentiles,
+    pub max_rss_kb: Percentiles,
+    pub output_bytes: Percentiles,
+}
+
+#[derive(Serialize, Debug)]
+pub struct Percentiles {
+    pub p50: u64,
+    pub p95: u64,
+    pub max: u64,
+}
+
+impl Percentiles {
+    fn of(mut values: Vec<u64>) -> Self {
+        values.sort_unstable();
+        let rank = |p: usize| {
+            let idx = (values.len() * p).div_ceil(100);
+            values[idx.max(1) - 1]
+        };
+        Percentiles {
+            p50: rank(50),
+            p95: rank(95),
+            max: values[values.len() - 1],
+        }
+    }
+}
+
+fn stats_file(image_id: &str) -> Option<PathBuf> {
+    let dir = dirs::data_local_dir()?
+        .join("ya-runtime-emscripten")
+        .join("stats");
+    Some(dir.join(format!("{:x}.jsonl", Sha256::digest(image_id.as_bytes()))))
+}
+
+pub fn record(image_id: &str, record: &RunRecord) -> anyhow::Result<()> {
+    let path = match stats_file(image_id) {
+        Some(path) => path,
+        None => return Ok(()),
+    };
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    writeln!(file, "{}", serde_json::to_string(record)?)?;
+    Ok(())
+}
+
+pub fn summarize(image_id: &str) -> anyhow::Result<BTreeMap<String, Summary>> {
+    let path = match stats_file(image_id) {
+        Some(path) if path.exists() => path,
+        _ => return Ok(BTreeMap::new()),
+    };
+    let mut records: BTreeMap<String, Vec<RunRecord>> = BTreeMap::new();
+    for line in BufReader::new(fs::File::open(path)?).lines() {
+        let record: RunRecord = serde_json::from_str(&line?)?;
+        records
+            .entry(record.entry_point.clone())
+            .or_default()
+            .push(record);
+    }
+    Ok(records
+        .into_iter()
+        .map(|(entry_point, records)| {
+            let summary = Summary {
+                runs: records.len(),
+                failures: records.iter().filter(|r| !r.success).count(),
+                duration_ms: Percentiles::of(records.iter().map(|r| r.duration_ms).collect()),
+                max_rss_kb: Percentiles::of(records.iter().map(|r| r.max_rss_kb).collect()),
+                output_bytes: Percentiles::of(records.iter().map(|r| r.output_bytes).collect()),
+            };
+            (entry_point, summary)
+        })
+        .collect())
+}
+
+#[cfg(unix)]
+fn rusage() -> Option<libc::rusage> {
+    // Safety: getrusage only writes into the provided struct.
+    unsafe {
+        let mut usage: libc::rusage = std::mem::zeroed();
+        if libc::getrusage(libc::RUSAGE_SELF, &mut usage) != 0 {
+            return None;
+        }
+        Some(usage)
+    }
+}
+
+/// Peak resident set size of this process in KiB.
+#[cfg(unix)]
+pub fn max_rss_kb() -> u64 {
+    rusage().map_or(0, |usage| usage.ru_maxrss as u64)
+}
+
+#[cfg(not(unix))]
+pub fn max_rss_kb() -> u64 {
+    0
+}
+
+/// User plus system CPU time consumed by this process.
+#[cfg(unix)]
+pub fn cpu_time() -> Duration {
+    let timeval = |tv: libc::timeval| {
+        Duration::from_secs(tv.tv_sec as u64) + Duration::from_micros(tv.tv_usec as u64)
+    };
+    rusage().map_or(Duration::default(), |usage| {
+        timeval(usage.ru_utime) + timeval(usage.ru_stime)
+    })
+}
+
+#[cfg(not(unix))]
+pub fn cpu_time() -> Duration {
+    Duration::default()
+}
@@ -0,0 +1,111 @@
+//! `status`, a summary of a workdir for supervisors reconciling their state
+//! after a restart.
+
+use crate::deploy::{self, DeployInfo};
+use crate::report::{self, SCHEMA_VERSION};
+use crate::{mounts, RuntimeImage, Workdir};
+use serde::Serialize;
+use std::fs;
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "kebab-case")]
+pub struct WorkdirStatus {
+    pub schema_version: u32,
+    /// Deployment finished and all its mount directories exist, and match
+    /// the image if one was given.
+    pub valid: bool,
+    pub problems: Vec<String>,
+    /// A deploy was interrupted and can be resumed.
+    pub deploy_in_progress: bool,
+    pub image_hash: Option<String>,
+    pub mounts: Vec<MountStatus>,
+    /// Most recent exec report.
+    pub last_run: Option<serde_json::Value>,
+    /// Total size of the workdir in bytes.
+    pub disk_usage: u64,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "kebab-case")]
+pub struct MountStatus {
+    pub guest_path: String,
+    pub dir: String,
+    pub exists: bool,
+    pub size: u64,
+}
+
+/// Collects the status of `workdir`, comparing it with `image` if given.
+/// Problems with the deployment are reported, not returned as errors.
+pub fn collect(workdir: &Workdir, image: Option<&RuntimeImage>) -> anyhow::Result<WorkdirStatus> {
+    let path = workdir.path();
+    let mut problems = Vec::new();
+
+    let image_hash = match DeployInfo::load(path) {
+        Ok(info) => info.image_hash,
+        Err(e) => {
+            problems.push(format!("{}: {}", deploy::DEPLOY_FILE, e));
+            None
+        }
+    };
+    let deploy_in_progress = path.join(deploy::JOURNAL_FILE).exists();
+    if deploy_in_progress {
+        problems.push("deploy did not finish".to_string());
+    }
+
+    let deployed = match workdir.mounts() {
+        Ok(deployed) => deployed,
+        Err(e) => {
+            problems.push(format!("{}: {}", deploy::MOUNTS_FILE, e));
+            Vec::new()
+        }
+    };
+    let mut mount_status = Vec::new();
+    for (dir, mount_point) in &deployed {
+        let host = path.join(dir);
+        let exists = host.is_dir();
+        if !exists {
+            problems.push(format!("directory of {} is missing", mount_point.path()));
+        }
+        mount_status.push(MountStatus {
+            guest_path: mount_point.path().to_string(),
+            dir: dir.clone(),
+            exists,
+            size: if exists { mounts::tree_size(&host)? } else { 0 },
+        });
+    }
+
+    if let Some(image) = image {
+        let declared = &image.manifest().mount_points;
+        if deployed.len() != declared.len()
+            || deployed.iter().zip(declared).any(|((_, a), b)| a != b)
+        {
+            problems.push("deployed mounts differ from the image's mount points".to_string());
+        }
+        let hash = image.hash()?;
+        if image_hash
+            .as_ref()
+            .is_some_and(|deployed| *deployed != hash)
+        {
+            problems.push(format!(
+                "workdir was deployed from a different image than {}",
+                hash
+            ));
+        }
+    }
+
+    let last_run = match report::latest(path)? {
+        Some(report) => Some(serde_json::from_slice(&fs::read(report)?)?),
+        None => None,
+    };
+
+    Ok(WorkdirStatus {
+        schema_version: SCHEMA_VERSION,
+        valid: problems.is_empty(),
+        problems,
+        deploy_in_progress,
+        image_hash,
+        mounts: mount_status,
+        last_run,
+        disk_usage: mounts::tree_size(path)?,
+    })
+}
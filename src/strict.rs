@@ -0,0 +1,29 @@
+//! Strict mode, which turns the runtime's permissive fallbacks into errors
+//! so image authors notice latent problems early.
+
+use crate::report::ErrorClass;
+use anyhow::anyhow;
+use log::warn;
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static STRICT: AtomicBool = AtomicBool::new(false);
+
+/// Enables strict mode for the rest of the process.
+pub fn enable() {
+    STRICT.store(true, Ordering::Relaxed);
+}
+
+pub fn is_enabled() -> bool {
+    STRICT.load(Ordering::Relaxed)
+}
+
+/// Reports a fallback identified by `code`: a warning by default, a
+/// `StrictMode` error in strict mode.
+pub fn fallback(code: &'static str, message: impl fmt::Display) -> anyhow::Result<()> {
+    if is_enabled() {
+        return Err(anyhow!("{} [{}]", message, code).context(ErrorClass::StrictMode));
+    }
+    warn!("{}", message);
+    Ok(())
+}
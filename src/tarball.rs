@@ -0,0 +1,179 @@
+//! `.tar.gz` task packages. Tarballs can't be read at random, so they are
+//! unpacked once into a cache directory keyed by their SHA-256 and used
+//! as unpacked packages from there.
+
+use crate::report::ErrorClass;
+use anyhow::anyhow;
+use flate2::read::GzDecoder;
+use sha2::{Digest, Sha256};
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use tar::EntryType;
+
+const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+
+/// Detected by the gzip magic bytes, whatever the name. Downloaded
+/// packages are cached under a name that doesn't tell their format.
+pub fn is_tar_gz(path: &Path) -> io::Result<bool> {
+    let mut magic = [0u8; 2];
+    let mut file = File::open(path)?;
+    Ok(file.read_exact(&mut magic).is_ok() && magic == GZIP_MAGIC)
+}
+
+/// Directory with the tarball's contents, unpacked on first use.
+pub fn unpacked(path: &Path) -> anyhow::Result<PathBuf> {
+    let mut hasher = Sha256::new();
+    io::copy(&mut File::open(path)?, &mut HashWriter(&mut hasher))?;
+    let cache = dirs::cache_dir()
+        .ok_or_else(|| anyhow!("no cache directory for unpacked packages"))?
+        .join("ya-runtime-emscripten")
+        .join("unpacked");
+    let dir = cache.join(format!("{:x}", hasher.result()));
+    if dir.is_dir() {
+        return Ok(dir);
+    }
+
+    fs::create_dir_all(&cache)?;
+    let tmp = cache.join(format!(".tmp-{}", uuid::Uuid::new_v4().to_hyphenated()));
+    fs::create_dir(&tmp)?;
+    if let Err(e) = unpack_into(path, &tmp) {
+        let _ = fs::remove_dir_all(&tmp);
+        return Err(e);
+    }
+    if let Err(e) = fs::rename(&tmp, &dir) {
+        let _ = fs::remove_dir_all(&tmp);
+        // Another process unpacked the same tarball first.
+        if !dir.is_dir() {
+            return Err(e.into());
+        }
+    }
+    Ok(dir)
+}
+
+fn unpack_into(path: &Path, dir: &Path) -> anyhow::Result<()> {
+    let mut tar = tar::Archive::new(GzDecoder::new(File::open(path)?));
+    for entry in tar.entries()? {
+        let mut entry = entry?;
+        let entry_type = entry.header().entry_type();
+        // Extension headers carry metadata and no content, `git archive`
+        // starts every tarball with a pax global header.
+        if is_extension(entry_type) {
+            continue;
+        }
+        // Links could point anywhere on the host, see `Package::check_entries`.
+        if !entry_type.is_file() && !entry_type.is_dir() {
+            return Err(anyhow!(
+                "{}: {} is not a regular file or directory",
+                path.display(),
+                entry.path()?.display()
+            )
+            .context(ErrorClass::BadImage));
+        }
+        // `unpack_in` refuses entries escaping the target directory.
+        if !entry.unpack_in(dir)? {
+            return Err(anyhow!("{}: entry outside of the package", path.display())
+                .context(ErrorClass::BadImage));
+        }
+    }
+    Ok(())
+}
+
+fn is_extension(entry_type: EntryType) -> bool {
+    match entry_type {
+        EntryType::XGlobalHeader
+        | EntryType::XHeader
+        | EntryType::GNULongName
+        | EntryType::GNULongLink => true,
+        _ => false,
+    }
+}
+
+struct HashWriter<'a>(&'a mut Sha256);
+
+impl io::Write for HashWriter<'_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.input(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+
+    fn tar_gz(name: &str, build: impl FnOnce(&mut tar::Builder<GzEncoder<File>>)) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("{}-{}.tar.gz", name, uuid::Uuid::new_v4()));
+        let mut builder = tar::Builder::new(GzEncoder::new(
+            File::create(&path).unwrap(),
+            Compression::default(),
+        ));
+        build(&mut builder);
+        builder.into_inner().unwrap().finish().unwrap();
+        path
+    }
+
+    fn append(
+        builder: &mut tar::Builder<GzEncoder<File>>,
+        entry_type: EntryType,
+        name: &str,
+        data: &[u8],
+    ) {
+        let mut header = tar::Header::new_ustar();
+        header.set_entry_type(entry_type);
+        header.set_size(data.len() as u64);
+        header.set_mode(0o644);
+        builder.append_data(&mut header, name, data).unwrap();
+    }
+
+    #[test]
+    fn test_git_archive() {
+        let path = tar_gz("git-archive", |builder| {
+            let comment = format!("52 comment={}\n", "0".repeat(40));
+            append(
+                builder,
+                EntryType::XGlobalHeader,
+                "pax_global_header",
+                comment.as_bytes(),
+            );
+            append(builder, EntryType::Directory, "pkg/", b"");
+            append(builder, EntryType::Regular, "pkg/manifest.json", b"{}");
+        });
+        let dir = path.with_extension("d");
+        fs::create_dir(&dir).unwrap();
+
+        unpack_into(&path, &dir).unwrap();
+        assert_eq!(fs::read(dir.join("pkg/manifest.json")).unwrap(), b"{}");
+        assert!(!dir.join("pax_global_header").exists());
+
+        fs::remove_dir_all(&dir).unwrap();
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_reject_links() {
+        let path = tar_gz("link", |builder| {
+            let mut header = tar::Header::new_ustar();
+            header.set_entry_type(EntryType::Symlink);
+            header.set_size(0);
+            header.set_link_name("/etc/passwd").unwrap();
+            builder
+                .append_data(&mut header, "manifest.json", io::empty())
+                .unwrap();
+        });
+        let dir = path.with_extension("d");
+        fs::create_dir(&dir).unwrap();
+
+        assert!(unpack_into(&path, &dir).is_err());
+        assert!(fs::read_dir(&dir).unwrap().next().is_none());
+
+        fs::remove_dir_all(&dir).unwrap();
+        fs::remove_file(&path).unwrap();
+    }
+}
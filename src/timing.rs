@@ -0,0 +1,75 @@
+//! Timing of command phases. A phase taking longer than its threshold is
+//! reported with a `slow-op` event, so operators can spot failing disks or
+//! overloaded nodes from logs.
+//!
+//! Thresholds come from `YA_EMSCRIPTEN_SLOW_OP_MS`: either one number of
+//! milliseconds for all phases, or a list like `engine-init=2000,default=500`.
+
+use crate::events;
+use log::debug;
+use std::time::{Duration, Instant};
+
+const SLOW_OP_ENV: &str = "YA_EMSCRIPTEN_SLOW_OP_MS";
+const DEFAULT_THRESHOLD: Duration = Duration::from_secs(1);
+
+/// Runs `f` as the phase `name`.
+pub fn phase<T>(name: &str, f: impl FnOnce() -> T) -> T {
+    let started = Instant::now();
+    let result = f();
+    let elapsed = started.elapsed();
+    debug!("{} took {:?}", name, elapsed);
+    let threshold = threshold(name, std::env::var(SLOW_OP_ENV).ok().as_deref());
+    if elapsed > threshold {
+        events::emit(
+            "slow-op",
+            &[
+                ("phase", &name),
+                ("duration-ms", &elapsed.as_millis()),
+                ("threshold-ms", &threshold.as_millis()),
+            ],
+        );
+    }
+    result
+}
+
+fn threshold(name: &str, config: Option<&str>) -> Duration {
+    let config = match config {
+        Some(config) => config,
+        None => return DEFAULT_THRESHOLD,
+    };
+    if let Ok(ms) = config.trim().parse() {
+        return Duration::from_millis(ms);
+    }
+    let mut default = DEFAULT_THRESHOLD;
+    for entry in config.split(',') {
+        let mut parts = entry.splitn(2, '=');
+        if let (Some(key), Some(Ok(ms))) = (parts.next(), parts.next().map(|v| v.trim().parse())) {
+            match key.trim() {
+                key if key == name => return Duration::from_millis(ms),
+                "default" => default = Duration::from_millis(ms),
+                _ => (),
+            }
+        }
+    }
+    default
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_threshold() {
+        assert_eq!(threshold("engine-init", None), DEFAULT_THRESHOLD);
+        assert_eq!(
+            threshold("engine-init", Some("250")),
+            Duration::from_millis(250)
+        );
+        let config = Some("engine-init=2000, default=500");
+        assert_eq!(threshold("engine-init", config), Duration::from_secs(2));
+        assert_eq!(
+            threshold("package-open", config),
+            Duration::from_millis(500)
+        );
+    }
+}
@@ -0,0 +1,78 @@
+//! External usage counter hooks, for billing on dimensions the runtime
+//! doesn't measure, e.g. `energy-estimate --exec-dir {}`.
+//!
+//! The command gets the exec report on stdin and `{}` replaced by the exec
+//! directory, and prints a JSON object mapping counter names to numbers.
+//! Names without the `golem.usage.` prefix get it added.
+
+use crate::scan::expand_args;
+use anyhow::{anyhow, bail};
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+pub const COUNTER_PREFIX: &str = "golem.usage.";
+
+#[derive(Debug, Clone)]
+pub struct CounterCommand {
+    template: Vec<String>,
+}
+
+impl std::str::FromStr for CounterCommand {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        let template: Vec<String> = s.split_whitespace().map(ToString::to_string).collect();
+        if template.is_empty() {
+            bail!("empty usage counter command");
+        }
+        Ok(CounterCommand { template })
+    }
+}
+
+impl CounterCommand {
+    pub fn collect(&self, exec_dir: &Path, report: &[u8]) -> anyhow::Result<BTreeMap<String, f64>> {
+        let args = expand_args(&self.template[1..], &exec_dir.display().to_string());
+        let mut child = Command::new(&self.template[0])
+            .args(&args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()?;
+        // The command may exit without reading its input.
+        let _ = child.stdin.take().unwrap().write_all(report);
+        let output = child.wait_with_output()?;
+        if !output.status.success() {
+            bail!("{} failed: {}", self.template[0], output.status);
+        }
+        parse_counters(&output.stdout)
+            .map_err(|e| anyhow!("{}: invalid counters: {}", self.template[0], e))
+    }
+}
+
+fn parse_counters(output: &[u8]) -> anyhow::Result<BTreeMap<String, f64>> {
+    let raw: BTreeMap<String, f64> = serde_json::from_slice(output)?;
+    Ok(raw
+        .into_iter()
+        .map(|(name, value)| {
+            if name.starts_with(COUNTER_PREFIX) {
+                (name, value)
+            } else {
+                (format!("{}{}", COUNTER_PREFIX, name), value)
+            }
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_counters() {
+        let counters = parse_counters(br#"{"energy_wh": 1.5, "golem.usage.gpu_sec": 2}"#).unwrap();
+        assert_eq!(counters["golem.usage.energy_wh"], 1.5);
+        assert_eq!(counters["golem.usage.gpu_sec"], 2.0);
+        assert!(parse_counters(br#"{"energy_wh": "a lot"}"#).is_err());
+    }
+}
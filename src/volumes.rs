@@ -0,0 +1,77 @@
+//! Persistent volumes, kept in the runtime's data dir per image and volume
+//! name, so activities of the same image can reuse e.g. caches.
+
+use crate::report::ErrorClass;
+use crate::{events, mounts, secure_dir};
+use anyhow::anyhow;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use ya_emscripten_meta::Volume;
+
+/// Host directory of `volume`, created empty on first use.
+pub fn open(image_hash: &str, volume: &Volume) -> anyhow::Result<PathBuf> {
+    // Also checked by `Manifest::validate`, the name becomes a directory.
+    let mut components = Path::new(&volume.name).components();
+    if !matches!(
+        (components.next(), components.next()),
+        (Some(Component::Normal(_)), None)
+    ) {
+        return Err(anyhow!("invalid volume name: {:?}", volume.name).context(ErrorClass::BadImage));
+    }
+    let image_dir = image_dir(image_hash)
+        .ok_or_else(|| anyhow!("no data directory for volume {}", volume.name))?;
+    let dir = image_dir.join(&volume.name);
+    if !dir.is_dir() {
+        fs::create_dir_all(&image_dir)?;
+        secure_dir::create_private_dir(&dir)?;
+        events::emit("volume.created", &[("volume", &volume.name)]);
+    }
+    Ok(dir)
+}
+
+/// Names and host directories of the existing volumes of an image.
+pub fn list(image_hash: &str) -> anyhow::Result<Vec<(String, PathBuf)>> {
+    let image_dir = match image_dir(image_hash) {
+        Some(dir) if dir.is_dir() => dir,
+        _ => return Ok(Vec::new()),
+    };
+    let mut volumes = Vec::new();
+    for entry in fs::read_dir(image_dir)? {
+        let entry = entry?;
+        if entry.file_type()?.is_dir() {
+            volumes.push((
+                entry.file_name().to_string_lossy().into_owned(),
+                entry.path(),
+            ));
+        }
+    }
+    volumes.sort();
+    Ok(volumes)
+}
+
+fn image_dir(image_hash: &str) -> Option<PathBuf> {
+    Some(
+        dirs::data_local_dir()?
+            .join("ya-runtime-emscripten")
+            .join("volumes")
+            .join(image_hash),
+    )
+}
+
+/// Fails if the volume grew past its quota. Its contents are kept, so the
+/// next run of the image sees them.
+pub fn check_quota(dir: &Path, volume: &Volume) -> anyhow::Result<()> {
+    if let Some(quota) = volume.quota {
+        let size = mounts::tree_size(dir)?;
+        if size > quota {
+            return Err(anyhow!(
+                "volume {} takes {} bytes, over its quota of {}",
+                volume.name,
+                size,
+                quota
+            )
+            .context(ErrorClass::OutputQuotaExceeded));
+        }
+    }
+    Ok(())
+}
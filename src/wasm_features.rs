@@ -0,0 +1,230 @@
+use anyhow::{anyhow, bail};
+use serde::Serialize;
+use std::collections::BTreeSet;
+use std::fmt;
+
+pub(crate) const WASM_MAGIC: &[u8] = b"\0asm";
+
+const SECTION_TYPE: u8 = 1;
+pub(crate) const SECTION_IMPORT: u8 = 2;
+const SECTION_TABLE: u8 = 4;
+pub(crate) const SECTION_MEMORY: u8 = 5;
+const SECTION_DATA_COUNT: u8 = 12;
+
+const VALTYPE_V128: u8 = 0x7b;
+const REFTYPE_EXTERNREF: u8 = 0x6f;
+
+pub(crate) const LIMITS_HAS_MAX: u8 = 0x01;
+const LIMITS_SHARED: u8 = 0x02;
+
+/// Post-MVP wasm features an entry point may depend on.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "kebab-case")]
+pub enum WasmFeature {
+    Simd,
+    Threads,
+    BulkMemory,
+    ReferenceTypes,
+}
+
+impl fmt::Display for WasmFeature {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            WasmFeature::Simd => "simd",
+            WasmFeature::Threads => "threads",
+            WasmFeature::BulkMemory => "bulk-memory",
+            WasmFeature::ReferenceTypes => "reference-types",
+        })
+    }
+}
+
+/// Features supported by the SpiderMonkey build of sp-wasm-engine.
+pub const ENGINE_FEATURES: &[WasmFeature] = &[];
+
+/// Features required by `wasm` and not supported by the engine.
+pub fn unsupported_features(wasm: &[u8]) -> anyhow::Result<Vec<WasmFeature>> {
+    Ok(required_features(wasm)?
+        .into_iter()
+        .filter(|feature| !ENGINE_FEATURES.contains(feature))
+        .collect())
+}
+
+/// Detects features from the module's declarations (types, imports, tables,
+/// memories and section layout). Instructions in function bodies are not
+/// decoded.
+pub fn required_features(wasm: &[u8]) -> anyhow::Result<BTreeSet<WasmFeature>> {
+    if wasm.len() < 8 || &wasm[..4] != WASM_MAGIC {
+        bail!("not a wasm module, no \\0asm magic");
+    }
+
+    let mut features = BTreeSet::new();
+    let mut r = Reader::new(&wasm[8..]);
+    while !r.is_empty() {
+        let id = r.u8()?;
+        let size = r.leb()? as usize;
+        let mut section = Reader::new(r.take(size)?);
+        match id {
+            SECTION_TYPE => {
+                for _ in 0..section.leb()? {
+                    section.u8()?;
+                    for _ in 0..2 {
+                        for _ in 0..section.leb()? {
+                            valtype(section.u8()?, &mut features);
+                        }
+                    }
+                }
+            }
+            SECTION_IMPORT => {
+                for _ in 0..section.leb()? {
+                    section.name()?;
+                    section.name()?;
+                    match section.u8()? {
+                        0 => {
+                            section.leb()?;
+                        }
+                        1 => {
+                            valtype(section.u8()?, &mut features);
+                            section.limits()?;
+                        }
+                        2 => {
+                            if section.limits()? & LIMITS_SHARED != 0 {
+                                features.insert(WasmFeature::Threads);
+                            }
+                        }
+                        3 => {
+                            valtype(section.u8()?, &mut features);
+                            section.u8()?;
+                        }
+                        // Exception tag: attribute and type index.
+                        4 => {
+                            section.u8()?;
+                            section.leb()?;
+                        }
+                        // Its length is unknown, the imports after it can't be read.
+                        kind => bail!("unknown import kind {}", kind),
+                    }
+                }
+            }
+            SECTION_TABLE => {
+                for _ in 0..section.leb()? {
+                    valtype(section.u8()?, &mut features);
+                    section.limits()?;
+                }
+            }
+            SECTION_MEMORY => {
+                for _ in 0..section.leb()? {
+                    if section.limits()? & LIMITS_SHARED != 0 {
+                        features.insert(WasmFeature::Threads);
+                    }
+                }
+            }
+            SECTION_DATA_COUNT => {
+                features.insert(WasmFeature::BulkMemory);
+            }
+            _ => (),
+        }
+    }
+    Ok(features)
+}
+
+fn valtype(ty: u8, features: &mut BTreeSet<WasmFeature>) {
+    match ty {
+        VALTYPE_V128 => {
+            features.insert(WasmFeature::Simd);
+        }
+        REFTYPE_EXTERNREF => {
+            features.insert(WasmFeature::ReferenceTypes);
+        }
+        _ => (),
+    }
+}
+
+pub(crate) struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    pub(crate) fn new(bytes: &'a [u8]) -> Self {
+        Reader { bytes, pos: 0 }
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.pos >= self.bytes.len()
+    }
+
+    pub(crate) fn take(&mut self, n: usize) -> anyhow::Result<&'a [u8]> {
+        let end = self
+            .pos
+            .checked_add(n)
+            .filter(|&end| end <= self.bytes.len())
+            .ok_or_else(|| anyhow!("truncated wasm module"))?;
+        let bytes = &self.bytes[self.pos..end];
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    pub(crate) fn u8(&mut self) -> anyhow::Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    pub(crate) fn leb(&mut self) -> anyhow::Result<u64> {
+        let mut value = 0u64;
+        for shift in (0..64).step_by(7) {
+            let byte = self.u8()?;
+            value |= u64::from(byte & 0x7f) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        bail!("invalid LEB128 in wasm module")
+    }
+
+    pub(crate) fn name(&mut self) -> anyhow::Result<&'a [u8]> {
+        let len = self.leb()? as usize;
+        self.take(len)
+    }
+
+    /// Reads limits, returns their flags.
+    fn limits(&mut self) -> anyhow::Result<u8> {
+        let flags = self.u8()?;
+        self.leb()?;
+        if flags & LIMITS_HAS_MAX != 0 {
+            self.leb()?;
+        }
+        Ok(flags)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_required_features() {
+        let mvp = b"\0asm\x01\0\0\0\x05\x03\x01\x00\x01";
+        assert!(required_features(mvp).unwrap().is_empty());
+
+        // Shared memory plus a data count section.
+        let threads = b"\0asm\x01\0\0\0\x05\x04\x01\x03\x01\x01\x0c\x01\x00";
+        let features: Vec<_> = required_features(threads).unwrap().into_iter().collect();
+        assert_eq!(
+            features,
+            vec![WasmFeature::Threads, WasmFeature::BulkMemory]
+        );
+        assert_eq!(
+            unsupported_features(threads).unwrap(),
+            vec![WasmFeature::Threads, WasmFeature::BulkMemory]
+        );
+
+        assert!(required_features(b"\0asm\x01\0\0\0\x05\x10").is_err());
+
+        // A tag import, then an imported shared memory.
+        let imports = b"\0asm\x01\0\0\0\x02\x10\x02\x01a\x01b\x04\0\0\x01a\x01c\x02\x03\x01\x01";
+        let features: Vec<_> = required_features(imports).unwrap().into_iter().collect();
+        assert_eq!(features, vec![WasmFeature::Threads]);
+
+        let unknown_kind = b"\0asm\x01\0\0\0\x02\x06\x01\x01a\x01b\x05";
+        assert!(required_features(unknown_kind).is_err());
+    }
+}
@@ -0,0 +1,249 @@
+use crate::deploy::{self, DeployInfo};
+use crate::image::RuntimeImage;
+use crate::preflight::FsCapabilities;
+use crate::report::ErrorClass;
+use crate::{events, extract_cache, mounts, normalize_path, secure_dir, strict, timing};
+use anyhow::{bail, Context};
+use log::{debug, info, warn};
+use std::fs;
+use std::path::{Path, PathBuf};
+use ya_emscripten_meta::MountPoint;
+
+/// Output directory of an earlier run in the same workdir, mounted
+/// read-only at `guest_path`.
+#[derive(Debug, Clone)]
+pub struct RunOutput {
+    pub exec_id: String,
+    pub guest_path: String,
+}
+
+impl std::str::FromStr for RunOutput {
+    type Err = anyhow::Error;
+
+    /// Parses `<exec-id>=<guest-path>`.
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        let mut parts = s.splitn(2, '=');
+        match (parts.next(), parts.next()) {
+            (Some(exec_id), Some(guest_path))
+                if uuid::Uuid::parse_str(exec_id).is_ok() && guest_path.starts_with('/') =>
+            {
+                Ok(RunOutput {
+                    exec_id: exec_id.to_string(),
+                    guest_path: guest_path.to_string(),
+                })
+            }
+            _ => bail!("expected <exec id>=<absolute guest path>, got {:?}", s),
+        }
+    }
+}
+
+/// Deployment directory of an image: mount directories, `mounts.json`,
+/// `deploy.json` and exec reports.
+#[derive(Debug, Clone)]
+pub struct Workdir {
+    path: PathBuf,
+}
+
+impl Workdir {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Workdir { path: path.into() }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn mounts(&self) -> anyhow::Result<Vec<(String, MountPoint)>> {
+        deploy::load_mounts(&self.path)
+    }
+
+    /// Maps a guest path to the host path backing it, if it is inside a mount.
+    pub fn resolve(&self, guest_path: &str) -> anyhow::Result<Option<PathBuf>> {
+        host_path(&self.path, &self.mounts()?, guest_path)
+    }
+
+    /// Directory with the captured output of an earlier run.
+    pub fn run_output_dir(&self, exec_id: &str) -> anyhow::Result<PathBuf> {
+        let dir = self.path.join(exec_id);
+        if !dir.is_dir() {
+            bail!("no output of run {} in {}", exec_id, self.path.display());
+        }
+        Ok(dir)
+    }
+
+    /// Total size of the writable mounts.
+    pub fn output_bytes(&self) -> anyhow::Result<u64> {
+        let mut size = 0;
+        for (dir, mount_point) in self.mounts()? {
+            if !matches!(mount_point, MountPoint::Ro(_)) {
+                size += mounts::tree_size(&self.path.join(dir))?;
+            }
+        }
+        Ok(size)
+    }
+
+    /// Number of files and directories in the writable mounts, and the guest
+    /// paths of the directories holding the most of them, largest first.
+    pub fn output_entries(&self, top: usize) -> anyhow::Result<(u64, Vec<(String, u64)>)> {
+        let mut total = 0;
+        let mut dirs = Vec::new();
+        for (dir, mount_point) in self.mounts()? {
+            if matches!(mount_point, MountPoint::Ro(_)) {
+                continue;
+            }
+            let root = self.path.join(dir);
+            let mut per_dir = Vec::new();
+            total += mounts::count_entries(&root, &mut per_dir)?;
+            for (host_dir, count) in per_dir {
+                let rest = host_dir.strip_prefix(&root)?;
+                let guest = Path::new(mount_point.path()).join(rest);
+                dirs.push((guest.display().to_string(), count));
+            }
+        }
+        dirs.sort_by_key(|(_, count)| std::cmp::Reverse(*count));
+        dirs.truncate(top);
+        Ok((total, dirs))
+    }
+
+    /// Creates a private directory for every mount point of the image.
+    /// An interrupted deploy of the same image is resumed.
+    pub fn deploy(&self, image: &RuntimeImage) -> anyhow::Result<()> {
+        events::emit("deploy.start", &[("workdir", &self.path.display())]);
+        let m = image.manifest();
+        m.validate().context(ErrorClass::BadImage)?;
+        let filesystem = FsCapabilities::probe(&self.path)?;
+        debug!("workdir filesystem: {:?}", filesystem);
+        if !filesystem.long_names {
+            strict::fallback(
+                "short-file-names",
+                "workdir filesystem does not support 255 byte file names",
+            )?;
+        }
+
+        let mut mounts = deploy::load_journal(&self.path)?;
+        if mounts.len() > m.mount_points.len()
+            || mounts
+                .iter()
+                .zip(&m.mount_points)
+                .any(|((_, done), declared)| done != declared)
+        {
+            bail!("unfinished deploy in workdir was started for a different image");
+        }
+        if !mounts.is_empty() {
+            info!("resuming deploy, {} mounts already created", mounts.len());
+        }
+        // Journal entries are written before their directory is created.
+        for (dir, _) in &mounts {
+            let full_path = self.path.join(dir);
+            let created = fs::symlink_metadata(&full_path)
+                .map(|meta| meta.is_dir())
+                .unwrap_or(false);
+            if !created {
+                secure_dir::create_private_dir(&full_path)?;
+            }
+        }
+
+        let total = m.mount_points.len();
+        for (step, mount_point) in m.mount_points.iter().enumerate().skip(mounts.len()) {
+            let id = uuid::Uuid::new_v4();
+            let id_str = id.to_hyphenated().to_string();
+            let full_path = self.path.join(&id_str);
+            mounts.push((id_str, mount_point.clone()));
+            deploy::save_journal(&self.path, &mounts)?;
+            secure_dir::create_private_dir(&full_path)?;
+            events::emit(
+                "deploy.progress",
+                &[("step", &(step + 1)), ("total", &total)],
+            );
+        }
+        deploy::save_mounts(&self.path, mounts)?;
+        let image_hash = timing::phase("image-hash", || image.hash())?;
+        let image_stamp = image.stamp()?;
+        if let Err(e) = timing::phase("extract", || extract_cache::populate(image, &image_hash)) {
+            warn!("failed to extract entry points: {}", e);
+        }
+        DeployInfo {
+            filesystem,
+            image_hash: Some(image_hash),
+            image_stamp,
+            image_id: Some(m.id.clone()),
+        }
+        .save(&self.path)?;
+        let journal = self.path.join(deploy::JOURNAL_FILE);
+        if journal.exists() {
+            fs::remove_file(journal)?;
+        }
+        events::emit("deploy.done", &[("workdir", &self.path.display())]);
+        Ok(())
+    }
+}
+
+pub(crate) fn host_path(
+    workdir: &Path,
+    mounts: &[(String, MountPoint)],
+    guest_path: &str,
+) -> anyhow::Result<Option<PathBuf>> {
+    let guest = normalize_path(guest_path)?;
+    let mut found = None;
+    for (dir, mount_point) in mounts {
+        let mount_path = normalize_path(mount_point.path())?;
+        if guest.starts_with(&mount_path) {
+            if found.is_some() {
+                strict::fallback(
+                    "ambiguous-mount",
+                    format!(
+                        "{} is inside more than one mount, using the first",
+                        guest_path
+                    ),
+                )?;
+                break;
+            }
+            found = Some(workdir.join(dir).join(guest.strip_prefix(&mount_path)?));
+        }
+    }
+    Ok(found)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_resume_deploy() {
+        let root = std::env::temp_dir().join(format!("deploy-{}", uuid::Uuid::new_v4()));
+        let package = root.join("package");
+        fs::create_dir_all(&package).unwrap();
+        let manifest = br#"{
+            "id": "test.golem.network/ls",
+            "name": "ls",
+            "runtime": "emscripten",
+            "main": { "id": "main", "wasm-path": "ls.wasm" },
+            "mount-points": [ { "rw": "/out" }, { "ro": "/in" } ]
+        }"#;
+        fs::write(package.join(crate::package::MANIFEST_NAME), &manifest[..]).unwrap();
+        fs::write(package.join("ls.wasm"), b"\0asm\x01\0\0\0").unwrap();
+        fs::write(package.join("ls.js"), b"").unwrap();
+        let image = RuntimeImage::open(&package).unwrap();
+
+        // Interrupted after journaling the first mount, before creating it.
+        let workdir = Workdir::new(root.join("workdir"));
+        fs::create_dir(workdir.path()).unwrap();
+        let first = (
+            "00000000-0000-0000-0000-000000000000".to_string(),
+            image.manifest().mount_points[0].clone(),
+        );
+        deploy::save_journal(workdir.path(), &[first.clone()]).unwrap();
+
+        workdir.deploy(&image).unwrap();
+        let mounts = workdir.mounts().unwrap();
+        assert_eq!(mounts.len(), 2);
+        assert_eq!(mounts[0], first);
+        assert_eq!(mounts[1].1, image.manifest().mount_points[1]);
+        for (dir, _) in &mounts {
+            assert!(workdir.path().join(dir).is_dir());
+        }
+        assert!(!workdir.path().join(deploy::JOURNAL_FILE).exists());
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -1,3 +1,7 @@
 mod manifest;
+mod sanitize;
+mod validate;
 
 pub use manifest::*;
+pub use sanitize::*;
+pub use validate::*;
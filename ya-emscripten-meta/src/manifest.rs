@@ -1,6 +1,7 @@
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 #[serde(rename_all = "kebab-case")]
 pub struct Manifest {
     /// Deployment id in url like form.
@@ -19,18 +20,66 @@ pub struct Manifest {
     #[serde(default)]
     #[serde(skip_serializing_if = "Option::is_none")]
     pub work_dir: Option<String>,
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub volumes: Vec<Volume>,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 #[serde(rename_all = "kebab-case")]
 pub struct EntryPoint {
     pub id: String,
     pub wasm_path: String,
     #[serde(default)]
     pub args_prefix: Vec<String>,
+    /// Native stack size (in bytes) of the thread running the engine.
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stack_size: Option<usize>,
+    /// Default wall-clock limit of a run, in seconds.
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timeout: Option<u64>,
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub health: Option<HealthProbe>,
+    /// Environment variables of the program, exec specs may override them.
+    #[serde(default)]
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub env: BTreeMap<String, String>,
+    /// Exit codes other than 0 that count as success, e.g. 1 for a diff
+    /// tool reporting differences.
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub success_codes: Vec<i32>,
+}
+
+/// Liveness check for long-running entry points: the program is healthy
+/// as long as it keeps touching `file`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub struct HealthProbe {
+    /// Guest path of the heartbeat file, inside a mount point.
+    pub file: String,
+    /// Seconds without modification after which the program is unhealthy.
+    pub timeout: u64,
+}
+
+/// Writable directory kept by the provider between activities of the same
+/// image, e.g. for caches.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub struct Volume {
+    pub name: String,
+    /// Guest path the volume is mounted at.
+    pub path: String,
+    /// Maximum size of the volume in bytes.
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub quota: Option<u64>,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 #[serde(rename_all = "kebab-case")]
 pub enum MountPoint {
     Ro(String),
@@ -38,6 +87,115 @@ pub enum MountPoint {
     Wo(String),
 }
 
+impl Manifest {
+    pub fn new(id: impl Into<String>, name: impl Into<String>, runtime: RuntimeType) -> Self {
+        Manifest {
+            id: id.into(),
+            name: name.into(),
+            main: None,
+            entry_points: Vec::new(),
+            runtime,
+            mount_points: Vec::new(),
+            work_dir: None,
+            volumes: Vec::new(),
+        }
+    }
+
+    pub fn main(mut self, main: EntryPoint) -> Self {
+        self.main = Some(main);
+        self
+    }
+
+    pub fn entry_point(mut self, entry_point: EntryPoint) -> Self {
+        self.entry_points.push(entry_point);
+        self
+    }
+
+    pub fn mount_point(mut self, mount_point: MountPoint) -> Self {
+        self.mount_points.push(mount_point);
+        self
+    }
+
+    pub fn work_dir(mut self, work_dir: impl Into<String>) -> Self {
+        self.work_dir = Some(work_dir.into());
+        self
+    }
+
+    pub fn volume(mut self, volume: Volume) -> Self {
+        self.volumes.push(volume);
+        self
+    }
+
+    /// Looks up entry point by id. Main entry point is not included.
+    pub fn find_entry_point(&self, id: &str) -> Option<&EntryPoint> {
+        self.entry_points.iter().find(|ep| ep.id == id)
+    }
+}
+
+impl EntryPoint {
+    pub fn new(id: impl Into<String>, wasm_path: impl Into<String>) -> Self {
+        EntryPoint {
+            id: id.into(),
+            wasm_path: wasm_path.into(),
+            args_prefix: Vec::new(),
+            stack_size: None,
+            timeout: None,
+            health: None,
+            env: BTreeMap::new(),
+            success_codes: Vec::new(),
+        }
+    }
+
+    pub fn args_prefix<It, S>(mut self, args: It) -> Self
+    where
+        It: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.args_prefix = args.into_iter().map(Into::into).collect();
+        self
+    }
+
+    pub fn stack_size(mut self, stack_size: usize) -> Self {
+        self.stack_size = Some(stack_size);
+        self
+    }
+
+    pub fn timeout(mut self, timeout: u64) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    pub fn health(mut self, health: HealthProbe) -> Self {
+        self.health = Some(health);
+        self
+    }
+
+    pub fn env(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.env.insert(key.into(), value.into());
+        self
+    }
+
+    pub fn success_codes(mut self, codes: impl IntoIterator<Item = i32>) -> Self {
+        self.success_codes = codes.into_iter().collect();
+        self
+    }
+}
+
+impl Volume {
+    pub fn new(name: impl Into<String>, path: impl Into<String>) -> Self {
+        Volume {
+            name: name.into(),
+            path: path.into(),
+            quota: None,
+        }
+    }
+
+    pub fn quota(mut self, quota: u64) -> Self {
+        self.quota = Some(quota);
+        self
+    }
+}
+
 impl MountPoint {
     pub fn path(&self) -> &str {
         match self {
@@ -46,9 +204,18 @@ impl MountPoint {
             MountPoint::Wo(path) => path,
         }
     }
+
+    /// `ro`, `rw` or `wo`, as in the manifest.
+    pub fn mode(&self) -> &'static str {
+        match self {
+            MountPoint::Ro(_) => "ro",
+            MountPoint::Rw(_) => "rw",
+            MountPoint::Wo(_) => "wo",
+        }
+    }
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 #[serde(rename_all = "kebab-case")]
 pub enum RuntimeType {
     Emscripten,
@@ -57,7 +224,7 @@ pub enum RuntimeType {
 
 #[cfg(test)]
 mod test {
-    use crate::manifest::Manifest;
+    use crate::manifest::{EntryPoint, Manifest, MountPoint, RuntimeType, Volume};
 
     #[test]
     fn test_manifest() {
@@ -73,4 +240,25 @@ mod test {
 
         eprintln!("m={:?}", m);
     }
+
+    #[test]
+    fn test_builder_round_trip() {
+        let m = Manifest::new("test.golem.network/ls", "ls", RuntimeType::Emscripten)
+            .main(EntryPoint::new("main", "bin/main.wasm"))
+            .entry_point(
+                EntryPoint::new("ls", "bin/ls.wasm")
+                    .args_prefix(vec!["-l"])
+                    .env("LANG", "C")
+                    .success_codes(vec![1]),
+            )
+            .mount_point(MountPoint::Rw("/out".into()))
+            .work_dir("/out")
+            .volume(Volume::new("cache", "/cache").quota(1 << 30));
+
+        let json = serde_json::to_string(&m).unwrap();
+        let m2: Manifest = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(m, m2);
+        assert_eq!(m2.find_entry_point("ls").unwrap().args_prefix, vec!["-l"]);
+    }
 }
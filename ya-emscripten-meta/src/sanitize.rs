@@ -0,0 +1,65 @@
+use crate::manifest::{Manifest, MountPoint};
+
+/// Manifest path rewritten by `Manifest::sanitize`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PathFix {
+    /// Manifest field, e.g. `entry-points[ls].wasm-path`.
+    pub field: String,
+    pub original: String,
+    pub fixed: String,
+}
+
+impl Manifest {
+    /// Converts Windows-style `\` separators in paths to `/`, returning what
+    /// was changed. Manifests authored on Windows would otherwise fail entry
+    /// lookup on Linux providers.
+    pub fn sanitize(&mut self) -> Vec<PathFix> {
+        let mut fixes = Vec::new();
+        if let Some(main) = &mut self.main {
+            fix(&mut fixes, "main.wasm-path".into(), &mut main.wasm_path);
+        }
+        for ep in &mut self.entry_points {
+            let field = format!("entry-points[{}].wasm-path", ep.id);
+            fix(&mut fixes, field, &mut ep.wasm_path);
+        }
+        for (idx, mount_point) in self.mount_points.iter_mut().enumerate() {
+            let path = match mount_point {
+                MountPoint::Ro(path) | MountPoint::Rw(path) | MountPoint::Wo(path) => path,
+            };
+            fix(&mut fixes, format!("mount-points[{}]", idx), path);
+        }
+        if let Some(work_dir) = &mut self.work_dir {
+            fix(&mut fixes, "work-dir".into(), work_dir);
+        }
+        fixes
+    }
+}
+
+fn fix(fixes: &mut Vec<PathFix>, field: String, path: &mut String) {
+    if path.contains('\\') {
+        let fixed = path.replace('\\', "/");
+        fixes.push(PathFix {
+            field,
+            original: std::mem::replace(path, fixed.clone()),
+            fixed,
+        });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{EntryPoint, Manifest, MountPoint, RuntimeType};
+
+    #[test]
+    fn test_sanitize() {
+        let mut m = Manifest::new("test", "test", RuntimeType::Emscripten)
+            .entry_point(EntryPoint::new("ls", "bin\\ls.wasm"))
+            .mount_point(MountPoint::Rw("/out".into()));
+
+        let fixes = m.sanitize();
+        assert_eq!(fixes.len(), 1);
+        assert_eq!(fixes[0].field, "entry-points[ls].wasm-path");
+        assert_eq!(m.entry_points[0].wasm_path, "bin/ls.wasm");
+        assert!(m.sanitize().is_empty());
+    }
+}
@@ -0,0 +1,353 @@
+use crate::manifest::{EntryPoint, Manifest, MountPoint, Volume};
+use std::collections::HashSet;
+use std::fmt;
+use std::mem;
+
+/// Guest path the task package itself is mounted at.
+pub const PACKAGE_MOUNT: &str = "@";
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ValidationError {
+    EmptyId,
+    EmptyEntryPointId,
+    DuplicateEntryPoint(String),
+    InvalidWasmPath {
+        entry_point: String,
+        path: String,
+    },
+    RelativeMountPoint(String),
+    RelativeWorkDir(String),
+    /// Mount point shadowing the package mount.
+    MountPointInPackage(String),
+    /// Mount point equal to or nested under a mount of a different mode.
+    ConflictingMountPoints {
+        outer: String,
+        inner: String,
+    },
+    /// Same path declared twice with the same mode.
+    DuplicateMountPoint(String),
+    /// Volume name that can't be used as a directory name.
+    InvalidVolumeName(String),
+    DuplicateVolume(String),
+    RelativeVolumePath(String),
+    /// Volume equal to, nested under or containing a mount point or another
+    /// volume.
+    VolumeOverlap {
+        volume: String,
+        path: String,
+    },
+    /// Path with `..` components, or an absolute path where a package
+    /// relative one is expected.
+    UnsafePath {
+        field: String,
+        path: String,
+    },
+}
+
+impl ValidationError {
+    /// Stable identifier of the problem for machine consumers.
+    pub fn code(&self) -> &'static str {
+        match self {
+            ValidationError::EmptyId => "empty-id",
+            ValidationError::EmptyEntryPointId => "empty-entry-point-id",
+            ValidationError::DuplicateEntryPoint(_) => "duplicate-entry-point",
+            ValidationError::InvalidWasmPath { .. } => "invalid-wasm-path",
+            ValidationError::RelativeMountPoint(_) => "relative-mount-point",
+            ValidationError::RelativeWorkDir(_) => "relative-work-dir",
+            ValidationError::MountPointInPackage(_) => "mount-point-in-package",
+            ValidationError::ConflictingMountPoints { .. } => "conflicting-mount-points",
+            ValidationError::DuplicateMountPoint(_) => "duplicate-mount-point",
+            ValidationError::InvalidVolumeName(_) => "invalid-volume-name",
+            ValidationError::DuplicateVolume(_) => "duplicate-volume",
+            ValidationError::RelativeVolumePath(_) => "relative-volume-path",
+            ValidationError::VolumeOverlap { .. } => "volume-overlap",
+            ValidationError::UnsafePath { .. } => "unsafe-path",
+        }
+    }
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ValidationError::EmptyId => write!(f, "manifest id is empty"),
+            ValidationError::EmptyEntryPointId => write!(f, "entry point with empty id"),
+            ValidationError::DuplicateEntryPoint(id) => write!(f, "duplicate entry point: {}", id),
+            ValidationError::InvalidWasmPath { entry_point, path } => write!(
+                f,
+                "entry point {}: wasm path {:?} does not point to a .wasm file",
+                entry_point, path
+            ),
+            ValidationError::RelativeMountPoint(path) => {
+                write!(f, "mount point {:?} is not an absolute path", path)
+            }
+            ValidationError::RelativeWorkDir(path) => {
+                write!(f, "work dir {:?} is not an absolute path", path)
+            }
+            ValidationError::MountPointInPackage(path) => write!(
+                f,
+                "mount point {:?} is inside the package mount {:?}",
+                path, PACKAGE_MOUNT
+            ),
+            ValidationError::ConflictingMountPoints { outer, inner } => write!(
+                f,
+                "mount point {:?} is inside {:?}, which has a different mode",
+                inner, outer
+            ),
+            ValidationError::DuplicateMountPoint(path) => {
+                write!(f, "duplicate mount point: {}", path)
+            }
+            ValidationError::InvalidVolumeName(name) => {
+                write!(f, "invalid volume name: {:?}", name)
+            }
+            ValidationError::DuplicateVolume(name) => write!(f, "duplicate volume: {}", name),
+            ValidationError::RelativeVolumePath(path) => {
+                write!(f, "volume path {:?} is not an absolute path", path)
+            }
+            ValidationError::VolumeOverlap { volume, path } => {
+                write!(f, "volume {} overlaps {:?}", volume, path)
+            }
+            ValidationError::UnsafePath { field, path } => {
+                write!(f, "{}: path {:?} escapes its root", field, path)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ValidationError {}
+
+impl Manifest {
+    /// Checks manifest consistency that serde alone cannot express.
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        match self.problems().into_iter().next() {
+            Some(e) => Err(e),
+            None => Ok(()),
+        }
+    }
+
+    /// Like `validate`, but reports every problem found instead of the first one.
+    pub fn problems(&self) -> Vec<ValidationError> {
+        let mut problems = Vec::new();
+        if self.id.is_empty() {
+            problems.push(ValidationError::EmptyId);
+        }
+
+        let mut ids = HashSet::new();
+        for ep in self.main.iter().chain(&self.entry_points) {
+            problems.extend(entry_point_problems(ep));
+            if !ids.insert(ep.id.as_str()) {
+                problems.push(ValidationError::DuplicateEntryPoint(ep.id.clone()));
+            }
+        }
+
+        for mount_point in &self.mount_points {
+            if !mount_point.path().starts_with('/') {
+                problems.push(ValidationError::RelativeMountPoint(
+                    mount_point.path().to_string(),
+                ));
+            }
+            if has_parent_dir(mount_point.path()) {
+                problems.push(ValidationError::UnsafePath {
+                    field: "mount-points".into(),
+                    path: mount_point.path().to_string(),
+                });
+            }
+            if components(mount_point.path()).first() == Some(&PACKAGE_MOUNT) {
+                problems.push(ValidationError::MountPointInPackage(
+                    mount_point.path().to_string(),
+                ));
+            }
+        }
+        for (i, outer) in self.mount_points.iter().enumerate() {
+            for (j, inner) in self.mount_points.iter().enumerate() {
+                if i != j
+                    && mem::discriminant(outer) != mem::discriminant(inner)
+                    && is_nested(inner, outer)
+                    // Equal paths are reported once.
+                    && (i < j || !is_nested(outer, inner))
+                {
+                    problems.push(ValidationError::ConflictingMountPoints {
+                        outer: outer.path().to_string(),
+                        inner: inner.path().to_string(),
+                    });
+                }
+                if i < j
+                    && mem::discriminant(outer) == mem::discriminant(inner)
+                    && components(outer.path()) == components(inner.path())
+                {
+                    problems.push(ValidationError::DuplicateMountPoint(
+                        inner.path().to_string(),
+                    ));
+                }
+            }
+        }
+
+        problems.extend(volume_problems(self));
+
+        if let Some(work_dir) = &self.work_dir {
+            if !work_dir.starts_with('/') {
+                problems.push(ValidationError::RelativeWorkDir(work_dir.clone()));
+            }
+            if has_parent_dir(work_dir) {
+                problems.push(ValidationError::UnsafePath {
+                    field: "work-dir".into(),
+                    path: work_dir.clone(),
+                });
+            }
+        }
+
+        problems
+    }
+}
+
+fn volume_problems(m: &Manifest) -> Vec<ValidationError> {
+    let mut problems = Vec::new();
+    let mut names = HashSet::new();
+    for (i, volume) in m.volumes.iter().enumerate() {
+        if !is_valid_volume_name(&volume.name) {
+            problems.push(ValidationError::InvalidVolumeName(volume.name.clone()));
+        }
+        if !names.insert(volume.name.as_str()) {
+            problems.push(ValidationError::DuplicateVolume(volume.name.clone()));
+        }
+        if !volume.path.starts_with('/') {
+            problems.push(ValidationError::RelativeVolumePath(volume.path.clone()));
+        }
+        if has_parent_dir(&volume.path) {
+            problems.push(ValidationError::UnsafePath {
+                field: format!("volumes[{}]", volume.name),
+                path: volume.path.clone(),
+            });
+        }
+        let others = m
+            .mount_points
+            .iter()
+            .map(MountPoint::path)
+            .chain(m.volumes[..i].iter().map(|volume| volume.path.as_str()))
+            .chain(std::iter::once(PACKAGE_MOUNT));
+        for path in others {
+            if overlaps(volume, path) {
+                problems.push(ValidationError::VolumeOverlap {
+                    volume: volume.name.clone(),
+                    path: path.to_string(),
+                });
+            }
+        }
+    }
+    problems
+}
+
+fn is_valid_volume_name(name: &str) -> bool {
+    !name.is_empty()
+        && name != "."
+        && name != ".."
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+}
+
+fn overlaps(volume: &Volume, path: &str) -> bool {
+    let (a, b) = (components(&volume.path), components(path));
+    a.starts_with(&b) || b.starts_with(&a)
+}
+
+/// Normal components of a guest path; `.` and repeated separators are ignored.
+fn components(path: &str) -> Vec<&str> {
+    path.split('/')
+        .filter(|part| !part.is_empty() && *part != ".")
+        .collect()
+}
+
+/// `inner` is equal to or below `outer`.
+fn is_nested(inner: &MountPoint, outer: &MountPoint) -> bool {
+    components(inner.path()).starts_with(&components(outer.path()))
+}
+
+fn entry_point_problems(ep: &EntryPoint) -> Vec<ValidationError> {
+    let mut problems = Vec::new();
+    if ep.id.is_empty() {
+        problems.push(ValidationError::EmptyEntryPointId);
+    }
+    if !ep.wasm_path.ends_with(".wasm") {
+        problems.push(ValidationError::InvalidWasmPath {
+            entry_point: ep.id.clone(),
+            path: ep.wasm_path.clone(),
+        });
+    }
+    if ep.wasm_path.starts_with(&['/', '\\'][..]) || has_parent_dir(&ep.wasm_path) {
+        problems.push(ValidationError::UnsafePath {
+            field: format!("entry-points[{}].wasm-path", ep.id),
+            path: ep.wasm_path.clone(),
+        });
+    }
+    if let Some(health) = &ep.health {
+        if has_parent_dir(&health.file) {
+            problems.push(ValidationError::UnsafePath {
+                field: format!("entry-points[{}].health.file", ep.id),
+                path: health.file.clone(),
+            });
+        }
+    }
+    problems
+}
+
+fn has_parent_dir(path: &str) -> bool {
+    path.split(&['/', '\\'][..]).any(|part| part == "..")
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{EntryPoint, Manifest, MountPoint, RuntimeType, ValidationError, Volume};
+
+    #[test]
+    fn test_validate() {
+        let m = Manifest::new("test", "test", RuntimeType::Emscripten)
+            .entry_point(EntryPoint::new("ls", "ls.wasm"))
+            .mount_point(MountPoint::Rw("/out".into()));
+        assert_eq!(m.validate(), Ok(()));
+
+        let m = m.entry_point(EntryPoint::new("ls", "ls2.wasm"));
+        assert_eq!(
+            m.validate(),
+            Err(ValidationError::DuplicateEntryPoint("ls".into()))
+        );
+
+        let m = m.mount_point(MountPoint::Ro("data".into()));
+        assert_eq!(m.problems().len(), 2);
+
+        let m = Manifest::new("test", "test", RuntimeType::Emscripten)
+            .mount_point(MountPoint::Ro("/@/data".into()))
+            .mount_point(MountPoint::Ro("/in".into()))
+            .mount_point(MountPoint::Rw("/in/tmp/".into()))
+            .mount_point(MountPoint::Rw("/out".into()))
+            .mount_point(MountPoint::Rw("/out/".into()))
+            .work_dir("/out");
+        let codes: Vec<_> = m.problems().iter().map(|e| e.code()).collect();
+        assert_eq!(
+            codes,
+            vec![
+                "mount-point-in-package",
+                "conflicting-mount-points",
+                "duplicate-mount-point"
+            ]
+        );
+
+        let m = Manifest::new("test", "test", RuntimeType::Emscripten)
+            .mount_point(MountPoint::Rw("/out".into()))
+            .volume(Volume::new("cache", "/cache"))
+            .volume(Volume::new("cache", "/out/cache"))
+            .volume(Volume::new("../up", "/up"));
+        let codes: Vec<_> = m.problems().iter().map(|e| e.code()).collect();
+        assert_eq!(
+            codes,
+            vec!["duplicate-volume", "volume-overlap", "invalid-volume-name"]
+        );
+
+        let m = Manifest::new("test", "test", RuntimeType::Emscripten)
+            .entry_point(EntryPoint::new("abs", "/etc/ls.wasm"))
+            .entry_point(EntryPoint::new("up", "bin/../../ls.wasm"))
+            .mount_point(MountPoint::Rw("/out/../..".into()))
+            .work_dir("/out/..");
+        let codes: Vec<_> = m.problems().iter().map(|e| e.code()).collect();
+        assert_eq!(codes, vec!["unsafe-path"; 4]);
+    }
+}